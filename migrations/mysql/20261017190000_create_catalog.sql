@@ -0,0 +1,55 @@
+CREATE TABLE IF NOT EXISTS backups (
+    uuid BINARY(16) PRIMARY KEY,
+    base_uuid BINARY(16),
+    `type` TINYINT UNSIGNED NOT NULL,
+    path VARCHAR(1024) NOT NULL,
+    size BIGINT NOT NULL,
+    created_at DATETIME(6) NOT NULL,
+    database_name VARCHAR(255),
+    binlog_file VARCHAR(255),
+    binlog_position BIGINT,
+    gtid_executed TEXT,
+    service VARCHAR(255),
+    checkpoint_type VARCHAR(32),
+    from_lsn BIGINT,
+    to_lsn BIGINT,
+    last_lsn BIGINT,
+    compression VARCHAR(16),
+    encryption_recipients TEXT,
+    remote_key TEXT,
+    checksum VARCHAR(64),
+    run_uuid BINARY(16),
+    host VARCHAR(255),
+    INDEX backups_service_created_at (service, created_at),
+    INDEX backups_base_uuid (base_uuid)
+);
+
+CREATE TABLE IF NOT EXISTS binlogs (
+    uuid BINARY(16) PRIMARY KEY,
+    service VARCHAR(255) NOT NULL,
+    file_name VARCHAR(255) NOT NULL,
+    path VARCHAR(1024) NOT NULL,
+    start_position BIGINT NOT NULL,
+    end_position BIGINT NOT NULL,
+    first_event_at DATETIME(6),
+    last_event_at DATETIME(6),
+    previous_gtids TEXT,
+    gtids TEXT,
+    size BIGINT NOT NULL,
+    created_at DATETIME(6) NOT NULL,
+    host VARCHAR(255),
+    UNIQUE binlogs_host_service_file_name (host, service, file_name)
+);
+
+CREATE TABLE IF NOT EXISTS runs (
+    uuid BINARY(16) PRIMARY KEY,
+    service VARCHAR(255) NOT NULL,
+    `trigger` VARCHAR(16) NOT NULL,
+    started_at DATETIME(6) NOT NULL,
+    finished_at DATETIME(6),
+    status VARCHAR(16) NOT NULL,
+    error TEXT,
+    stderr MEDIUMTEXT,
+    host VARCHAR(255),
+    INDEX runs_service_started_at (service, started_at)
+);
@@ -0,0 +1 @@
+ALTER TABLE backups ADD COLUMN database_name VARCHAR(255);
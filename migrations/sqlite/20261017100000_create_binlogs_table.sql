@@ -0,0 +1,15 @@
+CREATE TABLE IF NOT EXISTS binlogs (
+    uuid BINARY(16) PRIMARY KEY,
+    service VARCHAR(255) NOT NULL,
+    file_name VARCHAR(255) NOT NULL,
+    path VARCHAR(255) NOT NULL,
+    start_position BIGINT NOT NULL,
+    end_position BIGINT NOT NULL,
+    first_event_at DATETIME,
+    last_event_at DATETIME,
+    previous_gtids TEXT,
+    gtids TEXT,
+    size BIGINT NOT NULL,
+    created_at DATETIME NOT NULL,
+    UNIQUE (service, file_name)
+);
@@ -0,0 +1,3 @@
+ALTER TABLE backups ADD COLUMN binlog_file VARCHAR(255);
+ALTER TABLE backups ADD COLUMN binlog_position BIGINT;
+ALTER TABLE backups ADD COLUMN gtid_executed TEXT;
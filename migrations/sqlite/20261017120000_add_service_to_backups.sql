@@ -0,0 +1,2 @@
+ALTER TABLE backups ADD COLUMN service VARCHAR(255);
+CREATE INDEX IF NOT EXISTS backups_service_created_at ON backups (service, created_at);
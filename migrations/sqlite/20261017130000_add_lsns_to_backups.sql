@@ -0,0 +1,4 @@
+ALTER TABLE backups ADD COLUMN checkpoint_type VARCHAR(32);
+ALTER TABLE backups ADD COLUMN from_lsn BIGINT;
+ALTER TABLE backups ADD COLUMN to_lsn BIGINT;
+ALTER TABLE backups ADD COLUMN last_lsn BIGINT;
@@ -0,0 +1 @@
+ALTER TABLE backups ADD COLUMN compression VARCHAR(16);
@@ -0,0 +1 @@
+ALTER TABLE backups ADD COLUMN encryption_recipients TEXT;
@@ -0,0 +1 @@
+ALTER TABLE backups ADD COLUMN remote_key TEXT;
@@ -0,0 +1 @@
+ALTER TABLE backups ADD COLUMN checksum VARCHAR(64);
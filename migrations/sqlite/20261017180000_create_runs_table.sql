@@ -0,0 +1,13 @@
+CREATE TABLE IF NOT EXISTS runs (
+    uuid BINARY(16) PRIMARY KEY,
+    service VARCHAR(255) NOT NULL,
+    "trigger" VARCHAR(16) NOT NULL,
+    started_at DATETIME NOT NULL,
+    finished_at DATETIME,
+    status VARCHAR(16) NOT NULL,
+    error TEXT,
+    stderr TEXT
+);
+CREATE INDEX IF NOT EXISTS runs_service_started_at ON runs (service, started_at);
+
+ALTER TABLE backups ADD COLUMN run_uuid BINARY(16);
@@ -0,0 +1,3 @@
+ALTER TABLE backups ADD COLUMN host VARCHAR(255);
+ALTER TABLE runs ADD COLUMN host VARCHAR(255);
+ALTER TABLE binlogs ADD COLUMN host VARCHAR(255);
@@ -0,0 +1,2 @@
+ALTER TABLE backups ADD COLUMN job VARCHAR(255);
+ALTER TABLE runs ADD COLUMN job VARCHAR(255);
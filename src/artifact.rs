@@ -0,0 +1,175 @@
+use std::path::Path;
+use std::process::{ExitStatus, Stdio};
+use age::x25519::{Identity, Recipient};
+use tokio::fs;
+use tokio::fs::File;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::process::Command;
+use crate::checksum::{hex_digest, HashingWriter};
+use crate::compression::{create_decoder, create_encoder};
+use crate::config::{CompressionCodec, CompressionConfig};
+use crate::encryption::{create_decryptor, create_encryptor, EXTENSION};
+
+/// How much of the stream is kept around, enough for any dump header.
+const HEAD_LIMIT: usize = 1024 * 1024;
+
+/// How much of the end of the stream is kept around, enough for a dump trailer.
+const TAIL_LIMIT: usize = 4 * 1024;
+
+/// How much of stderr is kept, the end is what explains a failure.
+const STDERR_LIMIT: usize = 64 * 1024;
+
+/// Appends the compression and encryption extensions to `extension`, e.g. `sql` becomes `sql.zst.age`.
+pub fn artifact_extension(extension: &str, compression: Option<&CompressionConfig>, encrypted: bool) -> String {
+    let mut extension = extension.to_string();
+    if let Some(compression) = compression {
+        extension = format!("{}.{}", extension, compression.codec.extension());
+    }
+    if encrypted {
+        extension = format!("{}.{}", extension, EXTENSION);
+    }
+    extension
+}
+
+pub struct ArtifactOutput {
+    pub status: ExitStatus,
+    /// The start of the plaintext stream.
+    pub head: Vec<u8>,
+    /// The end of the plaintext stream.
+    pub tail: Vec<u8>,
+    /// The end of what the command wrote to stderr.
+    pub stderr: String,
+    /// SHA-256 of the file as written to disk.
+    pub checksum: String
+}
+
+/// Runs the command and writes its stdout into `path`, compressed and then encrypted when configured, so no plaintext
+/// touches the disk.
+///
+/// When writing fails the command is killed and the partial file removed.
+pub async fn run_to_artifact(cmd: &mut Command, path: &Path, compression: Option<&CompressionConfig>, recipients: &[Recipient]) -> Result<ArtifactOutput, std::io::Error> {
+    let result = write_artifact(cmd, path, compression, recipients).await;
+    if result.is_err() {
+        remove_artifact(path).await?;
+    }
+    result
+}
+
+async fn write_artifact(cmd: &mut Command, path: &Path, compression: Option<&CompressionConfig>, recipients: &[Recipient]) -> Result<ArtifactOutput, std::io::Error> {
+    let (file, hasher) = HashingWriter::new(File::create(path).await?);
+    let mut writer: Box<dyn AsyncWrite + Unpin + Send> = Box::new(file);
+    if !recipients.is_empty() {
+        writer = create_encryptor(writer, recipients).await?;
+    }
+    if let Some(compression) = compression {
+        writer = create_encoder(writer, compression);
+    }
+
+    let mut child = cmd.stdout(Stdio::piped()).stderr(Stdio::piped()).kill_on_drop(true).spawn()?;
+    let mut stdout = child.stdout.take().unwrap();
+    let stderr = tokio::spawn(read_stderr(child.stderr.take().unwrap()));
+    let mut head = vec![];
+    let mut tail = vec![];
+    let mut buffer = vec![0u8; 64 * 1024];
+    loop {
+        let read = stdout.read(&mut buffer).await?;
+        if read == 0 {
+            break;
+        }
+        if head.len() < HEAD_LIMIT {
+            head.extend_from_slice(&buffer[..read.min(HEAD_LIMIT - head.len())]);
+        }
+        tail.extend_from_slice(&buffer[..read]);
+        if tail.len() > TAIL_LIMIT {
+            tail.drain(..tail.len() - TAIL_LIMIT);
+        }
+        writer.write_all(&buffer[..read]).await?;
+    }
+    writer.shutdown().await?;
+
+    let checksum = hex_digest(hasher.lock().unwrap().clone());
+    let status = child.wait().await?;
+    let stderr = stderr.await.map_err(std::io::Error::other)??;
+    Ok(ArtifactOutput { status, head, tail, stderr, checksum })
+}
+
+/// Removes an artifact which can't be restored, e.g. one written by a failed run.
+pub async fn remove_artifact(path: &Path) -> Result<(), std::io::Error> {
+    match fs::remove_file(path).await {
+        Err(error) if error.kind() != std::io::ErrorKind::NotFound => Err(error),
+        _ => Ok(())
+    }
+}
+
+/// Collects stderr while the command runs, keeping only the end of it.
+pub async fn read_stderr<R: AsyncRead + Unpin>(mut stderr: R) -> Result<String, std::io::Error> {
+    let mut output = vec![];
+    let mut buffer = vec![0u8; 8 * 1024];
+    loop {
+        let read = stderr.read(&mut buffer).await?;
+        if read == 0 {
+            break;
+        }
+        output.extend_from_slice(&buffer[..read]);
+        if output.len() > STDERR_LIMIT {
+            output.drain(..output.len() - STDERR_LIMIT);
+        }
+    }
+    Ok(String::from_utf8_lossy(&output).to_string())
+}
+
+/// Opens an artifact for reading, decrypting and decompressing it on the fly based on its extensions.
+pub async fn open_artifact(path: &Path, identities: &[Identity]) -> Result<Box<dyn AsyncRead + Unpin + Send>, std::io::Error> {
+    let mut reader: Box<dyn AsyncRead + Unpin + Send> = Box::new(BufReader::new(File::open(path).await?));
+    let mut inner_path = path.to_path_buf();
+    if path.extension().is_some_and(|extension| extension == EXTENSION) {
+        if identities.is_empty() {
+            return Err(std::io::Error::other(format!("{} is encrypted, an identity file is needed to read it.", path.to_str().unwrap())));
+        }
+        reader = create_decryptor(BufReader::new(reader), identities).await?;
+        inner_path = path.with_extension("");
+    }
+    Ok(create_decoder(BufReader::new(reader), CompressionCodec::from_path(&inner_path)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::checksum::sha256_file;
+    use crate::config::CompressionCodec;
+
+    #[tokio::test]
+    async fn test_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let identity = Identity::generate();
+        let compression = CompressionConfig { codec: CompressionCodec::Zstd, level: None, threads: None };
+        let path = dir.path().join(format!("dump.{}", artifact_extension("sql", Some(&compression), true)));
+        assert!(path.to_str().unwrap().ends_with("dump.sql.zst.age"));
+
+        let mut cmd = Command::new("sh");
+        cmd.arg("-c").arg("echo '-- MySQL dump'; echo 'warning' >&2");
+        let output = run_to_artifact(&mut cmd, &path, Some(&compression), &[identity.to_public()]).await.unwrap();
+        assert!(output.status.success());
+        assert_eq!(output.head, b"-- MySQL dump\n");
+        assert_eq!(output.tail, b"-- MySQL dump\n");
+        assert_eq!(output.stderr, "warning\n");
+        assert_eq!(output.checksum, sha256_file(&path).await.unwrap());
+
+        let mut contents = String::new();
+        open_artifact(&path, &[identity]).await.unwrap().read_to_string(&mut contents).await.unwrap();
+        assert_eq!(contents, "-- MySQL dump\n");
+        assert!(open_artifact(&path, &[]).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_failed_write_removes_the_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("dump.sql.age");
+
+        // The file is created before the command is started, a command which fails to start leaves nothing behind.
+        let mut cmd = Command::new("definitely-not-a-command");
+        let error = run_to_artifact(&mut cmd, &path, None, &[]).await.err().unwrap();
+        assert_eq!(error.kind(), std::io::ErrorKind::NotFound);
+        assert!(!path.exists());
+    }
+}
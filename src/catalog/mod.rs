@@ -0,0 +1,91 @@
+pub mod mysql;
+pub mod sqlite;
+
+use std::path::Path;
+use async_trait::async_trait;
+use gethostname::gethostname;
+use uuid::Uuid;
+use crate::catalog::mysql::MySqlCatalog;
+use crate::catalog::sqlite::SqliteCatalog;
+use crate::config::CatalogConfig;
+use crate::service::mysql::database::{BinlogRow, MysqlBackupRow, RunRow};
+
+const DEFAULT_MAX_CONNECTIONS: u32 = 15;
+
+/// Where backups, runs and archived binary logs are recorded, implemented once per database dialect.
+///
+/// Queries which decide what happens to files on disk only see the rows written by this host, older rows
+/// without a host are treated as local.
+#[async_trait]
+pub trait Catalog: Send + Sync {
+    /// The name rows written through this catalog are recorded under.
+    fn host(&self) -> &str;
+
+    async fn insert_backup(&self, backup: &MysqlBackupRow) -> Result<(), sqlx::Error>;
+
+    async fn find_backup(&self, uuid: Uuid) -> Result<Option<MysqlBackupRow>, sqlx::Error>;
+
+    /// Records where the uploaded copy of a backup is stored.
+    async fn set_remote_key(&self, uuid: Uuid, remote_key: &str) -> Result<(), sqlx::Error>;
+
+    /// Lists the backups of a service, or of every service, oldest first.
+    async fn find_backups(&self, service: Option<&str>) -> Result<Vec<MysqlBackupRow>, sqlx::Error>;
+
+    /// Lists the backups this host took of a service, which are the ones its retention policy applies to.
+    async fn find_local_backups(&self, service: &str) -> Result<Vec<MysqlBackupRow>, sqlx::Error>;
+
+    /// Lists the backups taken directly on top of the given one.
+    async fn find_children(&self, base_uuid: Uuid) -> Result<Vec<MysqlBackupRow>, sqlx::Error>;
+
+    /// The xtrabackup one of a service's jobs took on this host which got furthest into the redo log.
+    async fn find_latest_xtrabackup(&self, service: &str, job: Option<&str>) -> Result<Option<MysqlBackupRow>, sqlx::Error>;
+
+    /// Hands the backups this host recorded before the catalog tracked services to `service`, returning how many there were.
+    async fn adopt_backups(&self, service: &str) -> Result<u64, sqlx::Error>;
+
+    /// Deletes the backups' rows in one transaction, so either all of them go or none do.
+    async fn delete_backups(&self, uuids: &[Uuid]) -> Result<(), sqlx::Error>;
+
+    async fn insert_run(&self, run: &RunRow) -> Result<(), sqlx::Error>;
+
+    /// Records how a run ended.
+    async fn update_run(&self, run: &RunRow) -> Result<(), sqlx::Error>;
+
+    async fn find_run(&self, uuid: Uuid) -> Result<Option<RunRow>, sqlx::Error>;
+
+    /// The newest finished run of a service on this host, optionally only one of `job` or one which ended with `status`.
+    async fn find_last_run(&self, service: &str, job: Option<&str>, status: Option<&str>) -> Result<Option<RunRow>, sqlx::Error>;
+
+    /// Counts the runs of a service on this host by status.
+    async fn count_runs(&self, service: &str) -> Result<Vec<(String, i64)>, sqlx::Error>;
+
+    /// Records an archived binary log, one which is already recorded is left alone so overlapping index runs don't fail.
+    async fn insert_binlog(&self, binlog: &BinlogRow) -> Result<(), sqlx::Error>;
+
+    /// Lists the binary logs this host archived of a service, ordered by file name.
+    async fn find_binlogs(&self, service: &str) -> Result<Vec<BinlogRow>, sqlx::Error>;
+}
+
+impl CatalogConfig {
+    /// The name this machine's rows are recorded under.
+    pub fn host(&self) -> String {
+        match &self.host {
+            Some(host) => host.clone(),
+            None => gethostname().to_string_lossy().to_string()
+        }
+    }
+
+    /// Whether the catalog lives in a MySQL or MariaDB server rather than in SQLite.
+    pub fn is_mysql(&self) -> bool {
+        self.url.as_ref().is_some_and(|url| url.starts_with("mysql:") || url.starts_with("mariadb:"))
+    }
+}
+
+/// Opens the catalog and brings its schema up to date, relative SQLite paths are resolved against `data_dir`.
+pub async fn connect(config: &CatalogConfig, data_dir: &Path) -> Result<Box<dyn Catalog>, Box<dyn std::error::Error>> {
+    if config.is_mysql() {
+        Ok(Box::new(MySqlCatalog::connect(config).await?))
+    } else {
+        Ok(Box::new(SqliteCatalog::connect(config, data_dir).await?))
+    }
+}
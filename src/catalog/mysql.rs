@@ -0,0 +1,294 @@
+use std::str::FromStr;
+use async_trait::async_trait;
+use sqlx::{MySql, Pool};
+use sqlx::migrate::Migrator;
+use sqlx::mysql::{MySqlConnectOptions, MySqlPoolOptions};
+use uuid::Uuid;
+use crate::catalog::{Catalog, DEFAULT_MAX_CONNECTIONS};
+use crate::config::CatalogConfig;
+use crate::service::mysql::database::{BinlogRow, MysqlBackupRow, RunRow};
+
+/// The schema, embedded so the binary does not depend on where it is started from.
+static MIGRATOR: Migrator = sqlx::migrate!("./migrations/mysql");
+
+/// A catalog in a MySQL or MariaDB database, which several hosts can share.
+pub struct MySqlCatalog {
+    pool: Pool<MySql>,
+    host: String
+}
+
+impl MySqlCatalog {
+    pub async fn connect(config: &CatalogConfig) -> Result<MySqlCatalog, Box<dyn std::error::Error>> {
+        let url = config.url.as_deref().unwrap_or_default();
+        let pool = MySqlPoolOptions::new()
+            .max_connections(config.max_connections.unwrap_or(DEFAULT_MAX_CONNECTIONS))
+            .connect_with(MySqlConnectOptions::from_str(url)?)
+            .await?;
+        MIGRATOR.run(&pool).await?;
+        Ok(MySqlCatalog { pool, host: config.host() })
+    }
+}
+
+#[async_trait]
+impl Catalog for MySqlCatalog {
+    fn host(&self) -> &str {
+        &self.host
+    }
+
+    async fn insert_backup(&self, backup: &MysqlBackupRow) -> Result<(), sqlx::Error> {
+        sqlx::query("INSERT INTO backups (uuid, base_uuid, `type`, path, size, created_at, database_name, binlog_file, binlog_position, gtid_executed, service, checkpoint_type, from_lsn, to_lsn, last_lsn, compression, encryption_recipients, remote_key, checksum, run_uuid, host, job) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)")
+            .bind(backup.uuid)
+            .bind(backup.base_uuid)
+            .bind(backup.backup_type)
+            .bind(&backup.path)
+            .bind(backup.size)
+            .bind(backup.created_at)
+            .bind(&backup.database_name)
+            .bind(&backup.binlog_file)
+            .bind(backup.binlog_position)
+            .bind(&backup.gtid_executed)
+            .bind(&backup.service)
+            .bind(&backup.checkpoint_type)
+            .bind(backup.from_lsn)
+            .bind(backup.to_lsn)
+            .bind(backup.last_lsn)
+            .bind(&backup.compression)
+            .bind(&backup.encryption_recipients)
+            .bind(&backup.remote_key)
+            .bind(&backup.checksum)
+            .bind(backup.run_uuid)
+            .bind(&backup.host)
+            .bind(&backup.job)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn find_backup(&self, uuid: Uuid) -> Result<Option<MysqlBackupRow>, sqlx::Error> {
+        sqlx::query_as("SELECT * FROM backups WHERE uuid = ?")
+            .bind(uuid)
+            .fetch_optional(&self.pool)
+            .await
+    }
+
+    async fn set_remote_key(&self, uuid: Uuid, remote_key: &str) -> Result<(), sqlx::Error> {
+        sqlx::query("UPDATE backups SET remote_key = ? WHERE uuid = ?")
+            .bind(remote_key)
+            .bind(uuid)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn find_backups(&self, service: Option<&str>) -> Result<Vec<MysqlBackupRow>, sqlx::Error> {
+        match service {
+            Some(service) => sqlx::query_as("SELECT * FROM backups WHERE service = ? ORDER BY created_at")
+                .bind(service)
+                .fetch_all(&self.pool)
+                .await,
+            None => sqlx::query_as("SELECT * FROM backups ORDER BY created_at")
+                .fetch_all(&self.pool)
+                .await
+        }
+    }
+
+    async fn find_local_backups(&self, service: &str) -> Result<Vec<MysqlBackupRow>, sqlx::Error> {
+        sqlx::query_as("SELECT * FROM backups WHERE service = ? AND (host = ? OR host IS NULL) ORDER BY created_at")
+            .bind(service)
+            .bind(&self.host)
+            .fetch_all(&self.pool)
+            .await
+    }
+
+    async fn find_children(&self, base_uuid: Uuid) -> Result<Vec<MysqlBackupRow>, sqlx::Error> {
+        sqlx::query_as("SELECT * FROM backups WHERE base_uuid = ?")
+            .bind(base_uuid)
+            .fetch_all(&self.pool)
+            .await
+    }
+
+    async fn find_latest_xtrabackup(&self, service: &str, job: Option<&str>) -> Result<Option<MysqlBackupRow>, sqlx::Error> {
+        sqlx::query_as("SELECT * FROM backups WHERE `type` = 1 AND service = ? AND (host = ? OR host IS NULL) AND job <=> ? AND to_lsn IS NOT NULL ORDER BY to_lsn DESC, created_at DESC LIMIT 1")
+            .bind(service)
+            .bind(&self.host)
+            .bind(job)
+            .fetch_optional(&self.pool)
+            .await
+    }
+
+    async fn adopt_backups(&self, service: &str) -> Result<u64, sqlx::Error> {
+        let result = sqlx::query("UPDATE backups SET service = ? WHERE service IS NULL AND (host = ? OR host IS NULL)")
+            .bind(service)
+            .bind(&self.host)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected())
+    }
+
+    async fn delete_backups(&self, uuids: &[Uuid]) -> Result<(), sqlx::Error> {
+        let mut transaction = self.pool.begin().await?;
+        for uuid in uuids {
+            sqlx::query("DELETE FROM backups WHERE uuid = ?")
+                .bind(uuid)
+                .execute(&mut *transaction)
+                .await?;
+        }
+        transaction.commit().await
+    }
+
+    async fn insert_run(&self, run: &RunRow) -> Result<(), sqlx::Error> {
+        sqlx::query("INSERT INTO runs (uuid, service, `trigger`, started_at, finished_at, status, error, stderr, host, job) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)")
+            .bind(run.uuid)
+            .bind(&run.service)
+            .bind(&run.trigger)
+            .bind(run.started_at)
+            .bind(run.finished_at)
+            .bind(&run.status)
+            .bind(&run.error)
+            .bind(&run.stderr)
+            .bind(&run.host)
+            .bind(&run.job)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn update_run(&self, run: &RunRow) -> Result<(), sqlx::Error> {
+        sqlx::query("UPDATE runs SET finished_at = ?, status = ?, error = ?, stderr = ? WHERE uuid = ?")
+            .bind(run.finished_at)
+            .bind(&run.status)
+            .bind(&run.error)
+            .bind(&run.stderr)
+            .bind(run.uuid)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn find_run(&self, uuid: Uuid) -> Result<Option<RunRow>, sqlx::Error> {
+        sqlx::query_as("SELECT * FROM runs WHERE uuid = ?")
+            .bind(uuid)
+            .fetch_optional(&self.pool)
+            .await
+    }
+
+    async fn find_last_run(&self, service: &str, job: Option<&str>, status: Option<&str>) -> Result<Option<RunRow>, sqlx::Error> {
+        sqlx::query_as("SELECT * FROM runs WHERE service = ? AND (host = ? OR host IS NULL) AND finished_at IS NOT NULL AND (? IS NULL OR job = ?) AND (? IS NULL OR status = ?) ORDER BY finished_at DESC LIMIT 1")
+            .bind(service)
+            .bind(&self.host)
+            .bind(job)
+            .bind(job)
+            .bind(status)
+            .bind(status)
+            .fetch_optional(&self.pool)
+            .await
+    }
+
+    async fn count_runs(&self, service: &str) -> Result<Vec<(String, i64)>, sqlx::Error> {
+        sqlx::query_as("SELECT status, COUNT(*) FROM runs WHERE service = ? AND (host = ? OR host IS NULL) GROUP BY status")
+            .bind(service)
+            .bind(&self.host)
+            .fetch_all(&self.pool)
+            .await
+    }
+
+    async fn insert_binlog(&self, binlog: &BinlogRow) -> Result<(), sqlx::Error> {
+        sqlx::query("INSERT INTO binlogs (uuid, service, file_name, path, start_position, end_position, first_event_at, last_event_at, previous_gtids, gtids, size, created_at, host) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?) ON DUPLICATE KEY UPDATE uuid = uuid")
+            .bind(binlog.uuid)
+            .bind(&binlog.service)
+            .bind(&binlog.file_name)
+            .bind(&binlog.path)
+            .bind(binlog.start_position)
+            .bind(binlog.end_position)
+            .bind(binlog.first_event_at)
+            .bind(binlog.last_event_at)
+            .bind(&binlog.previous_gtids)
+            .bind(&binlog.gtids)
+            .bind(binlog.size)
+            .bind(binlog.created_at)
+            .bind(&binlog.host)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn find_binlogs(&self, service: &str) -> Result<Vec<BinlogRow>, sqlx::Error> {
+        sqlx::query_as("SELECT * FROM binlogs WHERE service = ? AND (host = ? OR host IS NULL) ORDER BY file_name")
+            .bind(service)
+            .bind(&self.host)
+            .fetch_all(&self.pool)
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use uuid::{NoContext, Timestamp};
+
+    /// Connects to the server in `MYSQL_CATALOG_URL` as a host of its own, so runs never see each other's rows.
+    async fn connect() -> MySqlCatalog {
+        let url = std::env::var("MYSQL_CATALOG_URL").expect("MYSQL_CATALOG_URL must point at a scratch database");
+        let host = format!("test-{}", Uuid::new_v7(Timestamp::now(NoContext)));
+        MySqlCatalog::connect(&CatalogConfig { url: Some(url), host: Some(host), ..Default::default() }).await.unwrap()
+    }
+
+    fn backup(catalog: &MySqlCatalog, service: &str, hour: u128, to_lsn: i64) -> MysqlBackupRow {
+        MysqlBackupRow {
+            uuid: Uuid::new_v7(Timestamp::now(NoContext)),
+            service: Some(service.to_string()),
+            from_lsn: Some(0),
+            to_lsn: Some(to_lsn),
+            last_lsn: Some(to_lsn),
+            host: Some(catalog.host().to_string()),
+            ..MysqlBackupRow::test_default(hour)
+        }
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a MySQL server in MYSQL_CATALOG_URL"]
+    async fn test_backups() {
+        let catalog = connect().await;
+        let other = connect().await;
+        let service = format!("mysql-{}", catalog.host());
+
+        let first = backup(&catalog, &service, 1, 100);
+        let second = backup(&catalog, &service, 2, 200);
+        let remote = backup(&other, &service, 3, 300);
+        for row in [&first, &second, &remote] {
+            catalog.insert_backup(row).await.unwrap();
+        }
+
+        assert_eq!(catalog.find_backup(first.uuid).await.unwrap().unwrap().to_lsn, Some(100));
+        assert_eq!(catalog.find_backups(Some(&service)).await.unwrap().len(), 3);
+        let local = catalog.find_local_backups(&service).await.unwrap();
+        assert_eq!(local.iter().map(|backup| backup.uuid).collect::<Vec<_>>(), vec![first.uuid, second.uuid]);
+
+        // Another host's backup got further, but this host only chains onto its own.
+        assert_eq!(catalog.find_latest_xtrabackup(&service, None).await.unwrap().unwrap().uuid, second.uuid);
+        assert_eq!(other.find_latest_xtrabackup(&service, None).await.unwrap().unwrap().uuid, remote.uuid);
+
+        catalog.delete_backups(&[first.uuid, second.uuid, remote.uuid]).await.unwrap();
+        assert!(catalog.find_backups(Some(&service)).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a MySQL server in MYSQL_CATALOG_URL"]
+    async fn test_adopt_backups() {
+        let catalog = connect().await;
+        let other = connect().await;
+        let service = format!("mysql-{}", catalog.host());
+
+        let own = MysqlBackupRow { service: None, ..backup(&catalog, &service, 1, 100) };
+        let remote = MysqlBackupRow { service: None, ..backup(&other, &service, 2, 200) };
+        catalog.insert_backup(&own).await.unwrap();
+        catalog.insert_backup(&remote).await.unwrap();
+
+        // Every host adopts its own backups, never another's.
+        assert!(catalog.adopt_backups(&service).await.unwrap() >= 1);
+        assert_eq!(catalog.find_backup(own.uuid).await.unwrap().unwrap().service.as_deref(), Some(service.as_str()));
+        assert_eq!(catalog.find_backup(remote.uuid).await.unwrap().unwrap().service, None);
+
+        catalog.delete_backups(&[own.uuid, remote.uuid]).await.unwrap();
+    }
+}
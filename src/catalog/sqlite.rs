@@ -0,0 +1,370 @@
+use std::path::Path;
+use std::str::FromStr;
+use std::time::Duration;
+use async_trait::async_trait;
+use sqlx::{Pool, Sqlite};
+use sqlx::migrate::Migrator;
+use sqlx::sqlite::{SqliteConnectOptions, SqliteJournalMode, SqlitePoolOptions};
+use uuid::Uuid;
+use crate::catalog::{Catalog, DEFAULT_MAX_CONNECTIONS};
+use crate::config::{CatalogConfig, JournalMode};
+use crate::service::mysql::database::{BinlogRow, MysqlBackupRow, RunRow};
+
+const DEFAULT_FILE: &str = "sqlite.db";
+const DEFAULT_BUSY_TIMEOUT: u64 = 5;
+
+/// The schema, embedded so the binary does not depend on where it is started from.
+static MIGRATOR: Migrator = sqlx::migrate!("./migrations/sqlite");
+
+impl From<JournalMode> for SqliteJournalMode {
+    fn from(mode: JournalMode) -> SqliteJournalMode {
+        match mode {
+            JournalMode::Delete => SqliteJournalMode::Delete,
+            JournalMode::Truncate => SqliteJournalMode::Truncate,
+            JournalMode::Persist => SqliteJournalMode::Persist,
+            JournalMode::Memory => SqliteJournalMode::Memory,
+            JournalMode::Wal => SqliteJournalMode::Wal,
+            JournalMode::Off => SqliteJournalMode::Off
+        }
+    }
+}
+
+/// How to open the catalog, relative paths are resolved against `data_dir`.
+fn connect_options(config: &CatalogConfig, data_dir: &Path) -> Result<SqliteConnectOptions, sqlx::Error> {
+    let options = match (&config.url, &config.path) {
+        (Some(url), _) => SqliteConnectOptions::from_str(url)?,
+        (None, Some(path)) => SqliteConnectOptions::new().filename(data_dir.join(path)),
+        (None, None) => SqliteConnectOptions::new().filename(data_dir.join(DEFAULT_FILE))
+    };
+    Ok(options
+        .create_if_missing(true)
+        .journal_mode(config.journal_mode.unwrap_or(JournalMode::Wal).into())
+        .busy_timeout(Duration::from_secs(config.busy_timeout.unwrap_or(DEFAULT_BUSY_TIMEOUT))))
+}
+
+/// A catalog in a local SQLite database, the default.
+pub struct SqliteCatalog {
+    pool: Pool<Sqlite>,
+    host: String
+}
+
+impl SqliteCatalog {
+    pub async fn connect(config: &CatalogConfig, data_dir: &Path) -> Result<SqliteCatalog, Box<dyn std::error::Error>> {
+        let pool = SqlitePoolOptions::new()
+            .max_connections(config.max_connections.unwrap_or(DEFAULT_MAX_CONNECTIONS))
+            .connect_with(connect_options(config, data_dir)?)
+            .await?;
+        MIGRATOR.run(&pool).await?;
+        Ok(SqliteCatalog { pool, host: config.host() })
+    }
+}
+
+#[async_trait]
+impl Catalog for SqliteCatalog {
+    fn host(&self) -> &str {
+        &self.host
+    }
+
+    async fn insert_backup(&self, backup: &MysqlBackupRow) -> Result<(), sqlx::Error> {
+        sqlx::query("INSERT INTO backups (uuid, base_uuid, \"type\", path, size, created_at, database_name, binlog_file, binlog_position, gtid_executed, service, checkpoint_type, from_lsn, to_lsn, last_lsn, compression, encryption_recipients, remote_key, checksum, run_uuid, host, job) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17, $18, $19, $20, $21, $22)")
+            .bind(backup.uuid)
+            .bind(backup.base_uuid)
+            .bind(backup.backup_type)
+            .bind(&backup.path)
+            .bind(backup.size)
+            .bind(backup.created_at)
+            .bind(&backup.database_name)
+            .bind(&backup.binlog_file)
+            .bind(backup.binlog_position)
+            .bind(&backup.gtid_executed)
+            .bind(&backup.service)
+            .bind(&backup.checkpoint_type)
+            .bind(backup.from_lsn)
+            .bind(backup.to_lsn)
+            .bind(backup.last_lsn)
+            .bind(&backup.compression)
+            .bind(&backup.encryption_recipients)
+            .bind(&backup.remote_key)
+            .bind(&backup.checksum)
+            .bind(backup.run_uuid)
+            .bind(&backup.host)
+            .bind(&backup.job)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn find_backup(&self, uuid: Uuid) -> Result<Option<MysqlBackupRow>, sqlx::Error> {
+        sqlx::query_as("SELECT * FROM backups WHERE uuid = $1")
+            .bind(uuid)
+            .fetch_optional(&self.pool)
+            .await
+    }
+
+    async fn set_remote_key(&self, uuid: Uuid, remote_key: &str) -> Result<(), sqlx::Error> {
+        sqlx::query("UPDATE backups SET remote_key = $1 WHERE uuid = $2")
+            .bind(remote_key)
+            .bind(uuid)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn find_backups(&self, service: Option<&str>) -> Result<Vec<MysqlBackupRow>, sqlx::Error> {
+        match service {
+            Some(service) => sqlx::query_as("SELECT * FROM backups WHERE service = $1 ORDER BY created_at")
+                .bind(service)
+                .fetch_all(&self.pool)
+                .await,
+            None => sqlx::query_as("SELECT * FROM backups ORDER BY created_at")
+                .fetch_all(&self.pool)
+                .await
+        }
+    }
+
+    async fn find_local_backups(&self, service: &str) -> Result<Vec<MysqlBackupRow>, sqlx::Error> {
+        sqlx::query_as("SELECT * FROM backups WHERE service = $1 AND (host = $2 OR host IS NULL) ORDER BY created_at")
+            .bind(service)
+            .bind(&self.host)
+            .fetch_all(&self.pool)
+            .await
+    }
+
+    async fn find_children(&self, base_uuid: Uuid) -> Result<Vec<MysqlBackupRow>, sqlx::Error> {
+        sqlx::query_as("SELECT * FROM backups WHERE base_uuid = $1")
+            .bind(base_uuid)
+            .fetch_all(&self.pool)
+            .await
+    }
+
+    async fn find_latest_xtrabackup(&self, service: &str, job: Option<&str>) -> Result<Option<MysqlBackupRow>, sqlx::Error> {
+        sqlx::query_as("SELECT * FROM backups WHERE \"type\" = 1 AND service = $1 AND (host = $2 OR host IS NULL) AND job IS $3 AND to_lsn IS NOT NULL ORDER BY to_lsn DESC, created_at DESC LIMIT 1")
+            .bind(service)
+            .bind(&self.host)
+            .bind(job)
+            .fetch_optional(&self.pool)
+            .await
+    }
+
+    async fn adopt_backups(&self, service: &str) -> Result<u64, sqlx::Error> {
+        let result = sqlx::query("UPDATE backups SET service = $1 WHERE service IS NULL AND (host = $2 OR host IS NULL)")
+            .bind(service)
+            .bind(&self.host)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected())
+    }
+
+    async fn delete_backups(&self, uuids: &[Uuid]) -> Result<(), sqlx::Error> {
+        let mut transaction = self.pool.begin().await?;
+        for uuid in uuids {
+            sqlx::query("DELETE FROM backups WHERE uuid = $1")
+                .bind(uuid)
+                .execute(&mut *transaction)
+                .await?;
+        }
+        transaction.commit().await
+    }
+
+    async fn insert_run(&self, run: &RunRow) -> Result<(), sqlx::Error> {
+        sqlx::query("INSERT INTO runs (uuid, service, \"trigger\", started_at, finished_at, status, error, stderr, host, job) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)")
+            .bind(run.uuid)
+            .bind(&run.service)
+            .bind(&run.trigger)
+            .bind(run.started_at)
+            .bind(run.finished_at)
+            .bind(&run.status)
+            .bind(&run.error)
+            .bind(&run.stderr)
+            .bind(&run.host)
+            .bind(&run.job)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn update_run(&self, run: &RunRow) -> Result<(), sqlx::Error> {
+        sqlx::query("UPDATE runs SET finished_at = $1, status = $2, error = $3, stderr = $4 WHERE uuid = $5")
+            .bind(run.finished_at)
+            .bind(&run.status)
+            .bind(&run.error)
+            .bind(&run.stderr)
+            .bind(run.uuid)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn find_run(&self, uuid: Uuid) -> Result<Option<RunRow>, sqlx::Error> {
+        sqlx::query_as("SELECT * FROM runs WHERE uuid = $1")
+            .bind(uuid)
+            .fetch_optional(&self.pool)
+            .await
+    }
+
+    async fn find_last_run(&self, service: &str, job: Option<&str>, status: Option<&str>) -> Result<Option<RunRow>, sqlx::Error> {
+        sqlx::query_as("SELECT * FROM runs WHERE service = $1 AND (host = $2 OR host IS NULL) AND finished_at IS NOT NULL AND ($3 IS NULL OR job = $4) AND ($5 IS NULL OR status = $6) ORDER BY finished_at DESC LIMIT 1")
+            .bind(service)
+            .bind(&self.host)
+            .bind(job)
+            .bind(job)
+            .bind(status)
+            .bind(status)
+            .fetch_optional(&self.pool)
+            .await
+    }
+
+    async fn count_runs(&self, service: &str) -> Result<Vec<(String, i64)>, sqlx::Error> {
+        sqlx::query_as("SELECT status, COUNT(*) FROM runs WHERE service = $1 AND (host = $2 OR host IS NULL) GROUP BY status")
+            .bind(service)
+            .bind(&self.host)
+            .fetch_all(&self.pool)
+            .await
+    }
+
+    async fn insert_binlog(&self, binlog: &BinlogRow) -> Result<(), sqlx::Error> {
+        sqlx::query("INSERT INTO binlogs (uuid, service, file_name, path, start_position, end_position, first_event_at, last_event_at, previous_gtids, gtids, size, created_at, host) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13) ON CONFLICT DO NOTHING")
+            .bind(binlog.uuid)
+            .bind(&binlog.service)
+            .bind(&binlog.file_name)
+            .bind(&binlog.path)
+            .bind(binlog.start_position)
+            .bind(binlog.end_position)
+            .bind(binlog.first_event_at)
+            .bind(binlog.last_event_at)
+            .bind(&binlog.previous_gtids)
+            .bind(&binlog.gtids)
+            .bind(binlog.size)
+            .bind(binlog.created_at)
+            .bind(&binlog.host)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn find_binlogs(&self, service: &str) -> Result<Vec<BinlogRow>, sqlx::Error> {
+        sqlx::query_as("SELECT * FROM binlogs WHERE service = $1 AND (host = $2 OR host IS NULL) ORDER BY file_name")
+            .bind(service)
+            .bind(&self.host)
+            .fetch_all(&self.pool)
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sqlx::types::chrono::NaiveDate;
+    use tempfile::tempdir;
+
+    fn backup(uuid: u128, host: Option<&str>, to_lsn: i64) -> MysqlBackupRow {
+        MysqlBackupRow {
+            from_lsn: Some(0),
+            to_lsn: Some(to_lsn),
+            last_lsn: Some(to_lsn),
+            host: host.map(|host| host.to_string()),
+            ..MysqlBackupRow::test_default(uuid)
+        }
+    }
+
+    #[test]
+    fn test_connect_options() {
+        let data_dir = Path::new("/var/lib/backups");
+        let options = connect_options(&CatalogConfig::default(), data_dir).unwrap();
+        assert_eq!(options.get_filename(), Path::new("/var/lib/backups/sqlite.db"));
+
+        let config = CatalogConfig { path: Some("catalog.db".to_string()), ..Default::default() };
+        assert_eq!(connect_options(&config, data_dir).unwrap().get_filename(), Path::new("/var/lib/backups/catalog.db"));
+
+        let config = CatalogConfig { url: Some("sqlite:///srv/catalog.db".to_string()), path: Some("ignored.db".to_string()), ..Default::default() };
+        assert_eq!(connect_options(&config, data_dir).unwrap().get_filename(), Path::new("/srv/catalog.db"));
+    }
+
+    #[tokio::test]
+    async fn test_backups_are_scoped_to_the_host() {
+        let dir = tempdir().unwrap();
+        let config = CatalogConfig { host: Some("db1".to_string()), ..Default::default() };
+        let catalog = SqliteCatalog::connect(&config, dir.path()).await.unwrap();
+        assert!(dir.path().join("sqlite.db").exists());
+
+        catalog.insert_backup(&backup(1, None, 100)).await.unwrap();
+        catalog.insert_backup(&backup(2, Some("db1"), 200)).await.unwrap();
+        catalog.insert_backup(&backup(3, Some("db2"), 300)).await.unwrap();
+
+        assert_eq!(catalog.find_backups(Some("mysql-r1")).await.unwrap().len(), 3);
+        catalog.set_remote_key(Uuid::from_u128(1), "mysql-r1/1").await.unwrap();
+        assert_eq!(catalog.find_backup(Uuid::from_u128(1)).await.unwrap().unwrap().remote_key.as_deref(), Some("mysql-r1/1"));
+        let local = catalog.find_local_backups("mysql-r1").await.unwrap();
+        assert_eq!(local.iter().map(|backup| backup.uuid.as_u128()).collect::<Vec<_>>(), vec![1, 2]);
+        assert_eq!(catalog.find_latest_xtrabackup("mysql-r1", None).await.unwrap().unwrap().uuid, Uuid::from_u128(2));
+
+        // Every job builds its own chains.
+        catalog.insert_backup(&MysqlBackupRow { job: Some("weekly".to_string()), ..backup(4, Some("db1"), 400) }).await.unwrap();
+        assert_eq!(catalog.find_latest_xtrabackup("mysql-r1", None).await.unwrap().unwrap().uuid, Uuid::from_u128(2));
+        assert_eq!(catalog.find_latest_xtrabackup("mysql-r1", Some("weekly")).await.unwrap().unwrap().uuid, Uuid::from_u128(4));
+        assert!(catalog.find_latest_xtrabackup("mysql-r1", Some("hourly")).await.unwrap().is_none());
+        catalog.delete_backups(&[Uuid::from_u128(4)]).await.unwrap();
+
+        catalog.delete_backups(&[Uuid::from_u128(2)]).await.unwrap();
+        assert!(catalog.find_backup(Uuid::from_u128(2)).await.unwrap().is_none());
+        assert_eq!(catalog.find_latest_xtrabackup("mysql-r1", None).await.unwrap().unwrap().uuid, Uuid::from_u128(1));
+    }
+
+    #[tokio::test]
+    async fn test_delete_backups() {
+        let dir = tempdir().unwrap();
+        let catalog = SqliteCatalog::connect(&CatalogConfig::default(), dir.path()).await.unwrap();
+        for uuid in 1..=3 {
+            catalog.insert_backup(&backup(uuid, None, uuid as i64 * 100)).await.unwrap();
+        }
+
+        catalog.delete_backups(&[Uuid::from_u128(1), Uuid::from_u128(2)]).await.unwrap();
+        let left = catalog.find_local_backups("mysql-r1").await.unwrap();
+        assert_eq!(left.iter().map(|backup| backup.uuid.as_u128()).collect::<Vec<_>>(), vec![3]);
+        catalog.delete_backups(&[]).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_adopt_backups() {
+        let dir = tempdir().unwrap();
+        let catalog = SqliteCatalog::connect(&CatalogConfig::default(), dir.path()).await.unwrap();
+        catalog.insert_backup(&MysqlBackupRow { service: None, ..backup(1, None, 100) }).await.unwrap();
+        catalog.insert_backup(&backup(2, None, 200)).await.unwrap();
+        catalog.insert_backup(&MysqlBackupRow { service: None, ..backup(3, Some("db2"), 300) }).await.unwrap();
+        assert_eq!(catalog.find_local_backups("mysql-r1").await.unwrap().len(), 1);
+
+        // Backups taken before upgrading only become prunable once they belong to a service.
+        assert_eq!(catalog.adopt_backups("mysql-r1").await.unwrap(), 1);
+        assert_eq!(catalog.find_local_backups("mysql-r1").await.unwrap().len(), 2);
+        assert_eq!(catalog.adopt_backups("mysql-r1").await.unwrap(), 0);
+        assert_eq!(catalog.find_backup(Uuid::from_u128(3)).await.unwrap().unwrap().service, None);
+    }
+
+    #[tokio::test]
+    async fn test_binlog_is_recorded_once() {
+        let dir = tempdir().unwrap();
+        let catalog = SqliteCatalog::connect(&CatalogConfig::default(), dir.path()).await.unwrap();
+        let binlog = |uuid: u128| BinlogRow {
+            uuid: Uuid::from_u128(uuid),
+            service: "mysql-r1".to_string(),
+            file_name: "binlog.000001".to_string(),
+            path: "/srv/binlogs/mysql-r1/binlog.000001".to_string(),
+            start_position: 4,
+            end_position: 1024,
+            first_event_at: None,
+            last_event_at: None,
+            previous_gtids: None,
+            gtids: None,
+            size: 1024,
+            created_at: NaiveDate::from_ymd_opt(2024, 4, 16).unwrap().and_hms_opt(0, 0, 0).unwrap(),
+            host: None
+        };
+
+        // Overlapping index runs record the same file.
+        catalog.insert_binlog(&binlog(1)).await.unwrap();
+        catalog.insert_binlog(&binlog(2)).await.unwrap();
+        let binlogs = catalog.find_binlogs("mysql-r1").await.unwrap();
+        assert_eq!(binlogs.len(), 1);
+        assert_eq!(binlogs[0].uuid, Uuid::from_u128(1));
+    }
+}
@@ -0,0 +1,150 @@
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use sha2::{Digest, Sha256};
+use tokio::fs;
+use tokio::fs::File;
+use tokio::io::{AsyncReadExt, AsyncWrite};
+
+/// Lists every file of an xtrabackup directory with its SHA-256, in `sha256sum` format.
+pub const MANIFEST_FILE: &str = "manifest.sha256";
+
+/// Passes writes through while hashing exactly the bytes the inner writer accepted.
+pub struct HashingWriter<W> {
+    inner: W,
+    hasher: Arc<Mutex<Sha256>>
+}
+
+impl<W> HashingWriter<W> {
+    /// Returns the writer along with a handle to read the digest from once writing is done.
+    pub fn new(inner: W) -> (HashingWriter<W>, Arc<Mutex<Sha256>>) {
+        let hasher = Arc::new(Mutex::new(Sha256::new()));
+        (HashingWriter { inner, hasher: hasher.clone() }, hasher)
+    }
+}
+
+impl<W: AsyncWrite + Unpin> AsyncWrite for HashingWriter<W> {
+    fn poll_write(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<Result<usize, std::io::Error>> {
+        let poll = Pin::new(&mut self.inner).poll_write(cx, buf);
+        if let Poll::Ready(Ok(written)) = poll {
+            self.hasher.lock().unwrap().update(&buf[..written]);
+        }
+        poll
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), std::io::Error>> {
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), std::io::Error>> {
+        Pin::new(&mut self.inner).poll_shutdown(cx)
+    }
+}
+
+pub fn hex_digest(hasher: Sha256) -> String {
+    hasher.finalize().iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+pub async fn sha256_file(path: &Path) -> Result<String, std::io::Error> {
+    let mut file = File::open(path).await?;
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0u8; 64 * 1024];
+    loop {
+        let read = file.read(&mut buffer).await?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+    }
+    Ok(hex_digest(hasher))
+}
+
+/// Lists every file below `directory`, relative to it and without the manifest itself.
+async fn list_files(directory: &Path) -> Result<Vec<PathBuf>, std::io::Error> {
+    let mut files = vec![];
+    let mut pending = vec![PathBuf::new()];
+    while let Some(relative) = pending.pop() {
+        let mut entries = fs::read_dir(directory.join(&relative)).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            let entry_relative = relative.join(entry.file_name());
+            if entry.file_type().await?.is_dir() {
+                pending.push(entry_relative);
+            } else if entry_relative != Path::new(MANIFEST_FILE) {
+                files.push(entry_relative);
+            }
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// Hashes every file of the directory into its manifest, returning the checksum of the manifest.
+pub async fn write_manifest(directory: &Path) -> Result<String, std::io::Error> {
+    let mut manifest = String::new();
+    for relative in list_files(directory).await? {
+        let checksum = sha256_file(&directory.join(&relative)).await?;
+        manifest.push_str(&format!("{}  {}\n", checksum, relative.to_str().unwrap()));
+    }
+    let manifest_path = directory.join(MANIFEST_FILE);
+    fs::write(&manifest_path, manifest).await?;
+    sha256_file(&manifest_path).await
+}
+
+/// Re-hashes every file listed in the directory's manifest, returning a description of each mismatch.
+pub async fn verify_manifest(directory: &Path) -> Result<Vec<String>, std::io::Error> {
+    let manifest = fs::read_to_string(directory.join(MANIFEST_FILE)).await?;
+    let mut mismatches = vec![];
+    for line in manifest.lines() {
+        let Some((expected, relative)) = line.split_once("  ") else {
+            mismatches.push(format!("Malformed manifest line: {}", line));
+            continue;
+        };
+        let path = directory.join(relative);
+        if !path.is_file() {
+            mismatches.push(format!("{} is missing", relative));
+            continue;
+        }
+        let actual = sha256_file(&path).await?;
+        if actual != expected {
+            mismatches.push(format!("{} has checksum {}, expected {}", relative, actual, expected));
+        }
+    }
+    Ok(mismatches)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+    use tokio::io::AsyncWriteExt;
+
+    #[tokio::test]
+    async fn test_hashing_writer() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("dump.sql");
+        let (mut writer, hasher) = HashingWriter::new(File::create(&path).await.unwrap());
+        writer.write_all(b"abc").await.unwrap();
+        writer.shutdown().await.unwrap();
+
+        let checksum = hex_digest(hasher.lock().unwrap().clone());
+        assert_eq!(checksum, "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad");
+        assert_eq!(sha256_file(&path).await.unwrap(), checksum);
+    }
+
+    #[tokio::test]
+    async fn test_manifest() {
+        let dir = tempdir().unwrap();
+        std::fs::create_dir_all(dir.path().join("auth")).unwrap();
+        std::fs::write(dir.path().join("xtrabackup_checkpoints"), "backup_type = full-backuped").unwrap();
+        std::fs::write(dir.path().join("auth").join("users.ibd"), "data").unwrap();
+
+        let checksum = write_manifest(dir.path()).await.unwrap();
+        assert_eq!(sha256_file(&dir.path().join(MANIFEST_FILE)).await.unwrap(), checksum);
+        assert!(verify_manifest(dir.path()).await.unwrap().is_empty());
+
+        std::fs::write(dir.path().join("auth").join("users.ibd"), "rot").unwrap();
+        std::fs::remove_file(dir.path().join("xtrabackup_checkpoints")).unwrap();
+        assert_eq!(verify_manifest(dir.path()).await.unwrap().len(), 2);
+    }
+}
@@ -0,0 +1,177 @@
+use std::path::PathBuf;
+use std::process::ExitCode;
+use clap::{Args, Parser, Subcommand};
+use sqlx::types::chrono::{Local, NaiveDateTime, TimeZone};
+use uuid::Uuid;
+
+#[derive(Parser, Debug)]
+#[command(version, about)]
+pub struct Cli {
+    /// Configuration file, defaults to `config.toml` in the data directory.
+    #[arg(long, global = true)]
+    pub config: Option<PathBuf>,
+    /// Directory holding the configuration and the catalog, defaults to the directory of the executable.
+    #[arg(long, global = true)]
+    pub data_dir: Option<PathBuf>,
+    #[command(subcommand)]
+    pub command: Option<Commands>,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Commands {
+    /// Run the backup scheduler (the default).
+    Daemon,
+    /// Take a backup of a service right away.
+    Run(RunArgs),
+    /// List the backups in the catalog.
+    List(ListArgs),
+    /// Show a backup and the run which took it.
+    Show(ShowArgs),
+    /// Remove the backups the retention policies no longer keep.
+    Prune(PruneArgs),
+    /// Restore a backup recorded in the catalog.
+    Restore(RestoreArgs),
+    /// Delete a backup from disk and from the catalog.
+    Delete(DeleteArgs),
+    /// Re-hash backups and compare them with the checksums in the catalog.
+    Verify(VerifyArgs),
+    /// Inspect the configuration.
+    #[command(subcommand)]
+    Config(ConfigCommands),
+}
+
+/// Exit codes, so scripts can tell what went wrong without parsing the log.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ExitStatus {
+    /// The backup, restore or deletion itself failed.
+    Failure = 1,
+    /// The configuration or the arguments are invalid, clap uses the same code for the ones it rejects.
+    Config = 2,
+    /// The catalog could not be opened, migrated or queried.
+    Catalog = 3,
+    /// A backup or service given on the command line does not exist.
+    NotFound = 4,
+    /// Verification found corrupt or unreadable backups.
+    Corrupt = 5,
+    /// A backup of the service is already running.
+    Busy = 6,
+}
+
+impl From<ExitStatus> for ExitCode {
+    fn from(status: ExitStatus) -> ExitCode {
+        ExitCode::from(status as u8)
+    }
+}
+
+#[derive(Subcommand, Debug)]
+pub enum ConfigCommands {
+    /// Check the configuration for errors, including schedules and encryption keys.
+    Check,
+}
+
+#[derive(Args, Debug)]
+pub struct RunArgs {
+    /// Service to back up.
+    pub service: String,
+    /// Backup job to run, required when the service has several.
+    #[arg(long)]
+    pub job: Option<String>,
+    /// Take a full xtrabackup, even when the service takes incremental ones.
+    #[arg(long)]
+    pub full: bool,
+    /// Take an incremental xtrabackup on top of the latest backup.
+    #[arg(long, conflicts_with = "full")]
+    pub incremental: bool,
+}
+
+#[derive(Args, Debug)]
+pub struct ListArgs {
+    /// Only list the backups of this service.
+    #[arg(long)]
+    pub service: Option<String>,
+}
+
+#[derive(Args, Debug)]
+pub struct ShowArgs {
+    /// UUID of the backup to show.
+    pub uuid: Uuid,
+}
+
+#[derive(Args, Debug)]
+pub struct PruneArgs {
+    /// Only prune the backups of this service.
+    #[arg(long)]
+    pub service: Option<String>,
+    /// List what would be removed without removing it.
+    #[arg(long)]
+    pub dry_run: bool,
+}
+
+#[derive(Args, Debug)]
+pub struct VerifyArgs {
+    /// UUID of the backup to verify, every backup is verified when left out.
+    pub uuid: Option<Uuid>,
+    /// Only verify the backups of this service.
+    #[arg(long, conflicts_with = "uuid")]
+    pub service: Option<String>,
+    /// Verify the uploaded copies instead of the local ones.
+    #[arg(long)]
+    pub remote: bool,
+}
+
+#[derive(Args, Debug)]
+pub struct DeleteArgs {
+    /// UUID of the backup to delete.
+    pub uuid: Uuid,
+    /// Also delete every incremental backup taken on top of this one.
+    #[arg(long)]
+    pub with_dependents: bool,
+    /// Delete the backup even though incremental backups depend on it, leaving them unrestorable.
+    #[arg(long, conflicts_with = "with_dependents")]
+    pub force: bool,
+}
+
+#[derive(Args, Debug)]
+pub struct RestoreArgs {
+    /// UUID of the backup to restore.
+    pub uuid: Uuid,
+    /// Service whose connection settings are used as the restore target, defaults to the one that took the backup.
+    #[arg(long)]
+    pub service: Option<String>,
+    /// MySQL defaults file describing the restore target, overrides the service connection.
+    #[arg(long)]
+    pub defaults_file: Option<String>,
+    /// Restore into a different database than the one the backup was taken from.
+    #[arg(long)]
+    pub database: Option<String>,
+    /// Restore every table of a `separate_tables` dump, not only the selected one.
+    #[arg(long)]
+    pub all_tables: bool,
+    /// Directory an xtrabackup chain is copied to and prepared in, or a dump is downloaded to, and which is kept afterwards.
+    #[arg(long)]
+    pub staging_dir: Option<PathBuf>,
+    /// Copy the prepared xtrabackup back into this (empty) datadir.
+    #[arg(long)]
+    pub copy_back: Option<PathBuf>,
+    /// Roll forward with archived binary logs up to this local time, e.g. "2024-04-16 18:30:00".
+    #[arg(long, value_parser = parse_local_datetime)]
+    pub until: Option<NaiveDateTime>,
+    /// Roll forward with archived binary logs up to and including this GTID.
+    #[arg(long, conflicts_with = "until")]
+    pub until_gtid: Option<String>,
+    /// age identity file used to decrypt the backup, defaults to the configured `identity_file`.
+    #[arg(long)]
+    pub identity_file: Option<PathBuf>,
+    /// Skip restoring the backup itself and only replay binary logs, e.g. once a restored xtrabackup datadir is running.
+    #[arg(long)]
+    pub binlogs_only: bool,
+}
+
+/// Parses a local date time and converts it to UTC, which is what the catalog stores.
+fn parse_local_datetime(value: &str) -> Result<NaiveDateTime, String> {
+    let local = NaiveDateTime::parse_from_str(value, "%Y-%m-%d %H:%M:%S").map_err(|error| error.to_string())?;
+    match Local.from_local_datetime(&local).earliest() {
+        Some(local) => Ok(local.naive_utc()),
+        None => Err(format!("{} does not exist in the local time zone", value))
+    }
+}
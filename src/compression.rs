@@ -0,0 +1,105 @@
+use std::num::NonZeroU32;
+use std::path::Path;
+use async_compression::Level;
+use async_compression::tokio::bufread::{GzipDecoder, XzDecoder, ZstdDecoder};
+use async_compression::tokio::write::{GzipEncoder, XzEncoder, ZstdEncoder};
+use async_compression::zstd::CParameter;
+use tokio::io::{AsyncBufRead, AsyncRead, AsyncWrite};
+use crate::config::{CompressionCodec, CompressionConfig};
+
+impl CompressionCodec {
+    /// The name recorded in the catalog.
+    pub fn name(&self) -> &'static str {
+        match self {
+            CompressionCodec::Gzip => "gzip",
+            CompressionCodec::Zstd => "zstd",
+            CompressionCodec::Xz => "xz"
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            CompressionCodec::Gzip => "gz",
+            CompressionCodec::Zstd => "zst",
+            CompressionCodec::Xz => "xz"
+        }
+    }
+
+    /// Strips the codec extension off a file name, e.g. `users.sql.zst` becomes `users.sql`.
+    pub fn strip_extension(file_name: &str) -> &str {
+        match file_name.rsplit_once('.') {
+            Some((stem, "gz" | "zst" | "xz")) => stem,
+            _ => file_name
+        }
+    }
+
+    /// Figures out the codec of an artifact from its file extension.
+    pub fn from_path(path: &Path) -> Option<CompressionCodec> {
+        match path.extension()?.to_str()? {
+            "gz" => Some(CompressionCodec::Gzip),
+            "zst" => Some(CompressionCodec::Zstd),
+            "xz" => Some(CompressionCodec::Xz),
+            _ => None
+        }
+    }
+}
+
+/// Wraps `writer` in a streaming encoder, the caller has to `shutdown()` it to flush the trailer.
+pub fn create_encoder<W: AsyncWrite + Unpin + Send + 'static>(writer: W, config: &CompressionConfig) -> Box<dyn AsyncWrite + Unpin + Send> {
+    let level = config.level.map_or(Level::Default, Level::Precise);
+    let threads = config.threads.and_then(NonZeroU32::new);
+    match config.codec {
+        CompressionCodec::Gzip => Box::new(GzipEncoder::with_quality(writer, level)),
+        CompressionCodec::Zstd => match threads {
+            Some(threads) => Box::new(ZstdEncoder::with_quality_and_params(writer, level, &[CParameter::nb_workers(threads.get())])),
+            None => Box::new(ZstdEncoder::with_quality(writer, level))
+        },
+        CompressionCodec::Xz => match threads {
+            Some(threads) => Box::new(XzEncoder::parallel(writer, level, threads)),
+            None => Box::new(XzEncoder::with_quality(writer, level))
+        }
+    }
+}
+
+/// Wraps `reader` in a streaming decoder for `codec`, or passes it through when it is not compressed.
+pub fn create_decoder<R: AsyncBufRead + Unpin + Send + 'static>(reader: R, codec: Option<CompressionCodec>) -> Box<dyn AsyncRead + Unpin + Send> {
+    match codec {
+        Some(CompressionCodec::Gzip) => Box::new(GzipDecoder::new(reader)),
+        Some(CompressionCodec::Zstd) => Box::new(ZstdDecoder::new(reader)),
+        Some(CompressionCodec::Xz) => Box::new(XzDecoder::new(reader)),
+        None => Box::new(reader)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+    use tokio::fs::File;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt, BufReader};
+
+    #[tokio::test]
+    async fn test_round_trip() {
+        let dir = tempdir().unwrap();
+        for codec in [CompressionCodec::Gzip, CompressionCodec::Zstd, CompressionCodec::Xz] {
+            let path = dir.path().join(format!("dump.sql.{}", codec.extension()));
+            let config = CompressionConfig { codec, level: Some(3), threads: Some(2) };
+
+            let mut encoder = create_encoder(File::create(&path).await.unwrap(), &config);
+            encoder.write_all(b"CREATE TABLE test (id INT);\n").await.unwrap();
+            encoder.shutdown().await.unwrap();
+
+            let mut contents = String::new();
+            let reader = BufReader::new(File::open(&path).await.unwrap());
+            create_decoder(reader, CompressionCodec::from_path(&path)).read_to_string(&mut contents).await.unwrap();
+            assert_eq!(contents, "CREATE TABLE test (id INT);\n");
+            assert_eq!(CompressionCodec::from_path(&path), Some(codec));
+        }
+    }
+
+    #[test]
+    fn test_strip_extension() {
+        assert_eq!(CompressionCodec::strip_extension("auth.users.sql.zst"), "auth.users.sql");
+        assert_eq!(CompressionCodec::strip_extension("auth.users.sql"), "auth.users.sql");
+    }
+}
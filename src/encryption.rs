@@ -0,0 +1,111 @@
+use std::path::Path;
+use std::str::FromStr;
+use age::x25519::{Identity, Recipient};
+use age::{Decryptor, Encryptor};
+use tokio::fs;
+use tokio::io::{AsyncBufRead, AsyncRead, AsyncWrite};
+use tokio_util::compat::{FuturesAsyncReadCompatExt, FuturesAsyncWriteCompatExt, TokioAsyncReadCompatExt, TokioAsyncWriteCompatExt};
+use crate::config::EncryptionConfig;
+
+/// Extension appended to every encrypted artifact.
+pub const EXTENSION: &str = "age";
+
+impl EncryptionConfig {
+    pub fn parse_recipients(&self) -> Result<Vec<Recipient>, String> {
+        if self.recipients.is_empty() {
+            return Err("Encryption is enabled but no recipients are configured.".to_string());
+        }
+        self.recipients.iter()
+            .map(|recipient| Recipient::from_str(recipient.trim()).map_err(|error| format!("Invalid age recipient {}: {}", recipient, error)))
+            .collect()
+    }
+
+    /// Loads the private keys from `identity_file`.
+    pub async fn read_identities(&self) -> Result<Vec<Identity>, String> {
+        match &self.identity_file {
+            Some(identity_file) => read_identities(Path::new(identity_file)).await,
+            None => Err("Encrypted backups need an identity_file to be decrypted.".to_string())
+        }
+    }
+}
+
+/// What the catalog records about who can decrypt an artifact, `None` when it is not encrypted.
+pub fn join_recipients(recipients: &[Recipient]) -> Option<String> {
+    if recipients.is_empty() {
+        return None;
+    }
+    Some(recipients.iter().map(|recipient| recipient.to_string()).collect::<Vec<String>>().join(","))
+}
+
+/// Reads the `AGE-SECRET-KEY-` lines of an age identity file, comments and blank lines are skipped.
+pub async fn read_identities(path: &Path) -> Result<Vec<Identity>, String> {
+    let contents = fs::read_to_string(path).await.map_err(|error| format!("Unable to read {}: {}", path.to_str().unwrap(), error))?;
+    let identities = contents.lines()
+        .map(|line| line.trim())
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| Identity::from_str(line).map_err(|error| format!("Invalid age identity in {}: {}", path.to_str().unwrap(), error)))
+        .collect::<Result<Vec<Identity>, String>>()?;
+    if identities.is_empty() {
+        return Err(format!("No age identities found in {}.", path.to_str().unwrap()));
+    }
+    Ok(identities)
+}
+
+/// Wraps `writer` so everything written to it is encrypted to the recipients, the caller has to `shutdown()` it to write the last chunk.
+pub async fn create_encryptor<W: AsyncWrite + Unpin + Send + 'static>(writer: W, recipients: &[Recipient]) -> Result<Box<dyn AsyncWrite + Unpin + Send>, std::io::Error> {
+    let encryptor = Encryptor::with_recipients(recipients.iter().map(|recipient| recipient as &dyn age::Recipient))
+        .map_err(std::io::Error::other)?;
+    let writer = encryptor.wrap_async_output(writer.compat_write()).await?;
+    Ok(Box::new(writer.compat_write()))
+}
+
+/// Wraps `reader` so it yields the plaintext, using whichever identity the file was encrypted to.
+pub async fn create_decryptor<R: AsyncBufRead + Unpin + Send + 'static>(reader: R, identities: &[Identity]) -> Result<Box<dyn AsyncRead + Unpin + Send>, std::io::Error> {
+    let decryptor = Decryptor::new_async_buffered(reader.compat()).await.map_err(std::io::Error::other)?;
+    let reader = decryptor.decrypt_async(identities.iter().map(|identity| identity as &dyn age::Identity))
+        .map_err(std::io::Error::other)?;
+    Ok(Box::new(reader.compat()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use age::secrecy::ExposeSecret;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt, BufReader};
+
+    #[tokio::test]
+    async fn test_round_trip() {
+        let identity = Identity::generate();
+        let dir = tempfile::tempdir().unwrap();
+        let identity_path = dir.path().join("key.txt");
+        std::fs::write(&identity_path, format!("# created: today\n{}\n", identity.to_string().expose_secret())).unwrap();
+
+        let config = EncryptionConfig {
+            recipients: vec![identity.to_public().to_string()],
+            identity_file: Some(identity_path.to_str().unwrap().to_string())
+        };
+        let path = dir.path().join("dump.sql.age");
+        let mut writer = create_encryptor(fs::File::create(&path).await.unwrap(), &config.parse_recipients().unwrap()).await.unwrap();
+        writer.write_all(b"INSERT INTO users VALUES (1);\n").await.unwrap();
+        writer.shutdown().await.unwrap();
+        assert!(!std::fs::read(&path).unwrap().starts_with(b"INSERT"));
+
+        let identities = config.read_identities().await.unwrap();
+        let mut reader = create_decryptor(BufReader::new(fs::File::open(&path).await.unwrap()), &identities).await.unwrap();
+        let mut contents = String::new();
+        reader.read_to_string(&mut contents).await.unwrap();
+        assert_eq!(contents, "INSERT INTO users VALUES (1);\n");
+
+        // A different key cannot read it.
+        let other = [Identity::generate()];
+        assert!(create_decryptor(BufReader::new(fs::File::open(&path).await.unwrap()), &other).await.is_err());
+    }
+
+    #[test]
+    fn test_parse_recipients() {
+        let config = EncryptionConfig { recipients: vec!["age1invalid".to_string()], identity_file: None };
+        assert!(config.parse_recipients().is_err());
+        let config = EncryptionConfig { recipients: vec![], identity_file: None };
+        assert!(config.parse_recipients().is_err());
+    }
+}
@@ -0,0 +1,86 @@
+use std::process::Stdio;
+use std::time::Duration;
+use log::debug;
+use tokio::process::Command;
+use tokio::time::timeout;
+use crate::utils::ToolError;
+
+/// How long a hook may run when the service does not set `hook_timeout`.
+pub const DEFAULT_HOOK_TIMEOUT: Duration = Duration::from_secs(5 * 60);
+
+/// Commands the configuration can run around a backup.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Hook {
+    /// Before the backup, failing aborts the run.
+    PreBackup,
+    /// After the backup, whether it succeeded or not.
+    PostBackup,
+    OnSuccess,
+    OnFailure
+}
+
+impl Hook {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Hook::PreBackup => "pre_backup",
+            Hook::PostBackup => "post_backup",
+            Hook::OnSuccess => "on_success",
+            Hook::OnFailure => "on_failure"
+        }
+    }
+}
+
+fn shell_command(command: &str) -> Command {
+    if cfg!(target_os = "windows") {
+        let mut cmd = Command::new("cmd");
+        cmd.arg("/C").arg(command);
+        cmd
+    } else {
+        let mut cmd = Command::new("sh");
+        cmd.arg("-c").arg(command);
+        cmd
+    }
+}
+
+/// Runs a hook through the shell with the given environment, killing it once `limit` passes.
+pub async fn run_hook(hook: Hook, command: &str, env: &[(&str, String)], limit: Duration) -> Result<(), Box<dyn std::error::Error>> {
+    debug!("Running {} hook: {}", hook.as_str(), command);
+    let mut cmd = shell_command(command);
+    cmd.envs(env.iter().map(|(name, value)| (*name, value)))
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true);
+
+    let output = match timeout(limit, cmd.spawn()?.wait_with_output()).await {
+        Ok(output) => output?,
+        Err(_) => return Err(format!("{} hook did not finish within {:?}.", hook.as_str(), limit).into())
+    };
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    if !stdout.trim().is_empty() {
+        debug!("{} hook output: {}", hook.as_str(), stdout.trim());
+    }
+    if !output.status.success() {
+        let message = format!("{} hook failed with {}", hook.as_str(), output.status);
+        return Err(ToolError::new(message, String::from_utf8_lossy(&output.stderr).to_string()).into());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_run_hook() {
+        let env = [("MYSQL_BACKUP_SERVICE", "mysql-r1".to_string())];
+        assert!(run_hook(Hook::PreBackup, "test \"$MYSQL_BACKUP_SERVICE\" = mysql-r1", &env, DEFAULT_HOOK_TIMEOUT).await.is_ok());
+
+        let error = run_hook(Hook::PreBackup, "echo 'replica is busy' >&2; exit 3", &env, DEFAULT_HOOK_TIMEOUT).await.unwrap_err();
+        assert_eq!(error.downcast_ref::<ToolError>().unwrap().stderr, "replica is busy\n");
+        assert_eq!(error.to_string(), "pre_backup hook failed with exit status: 3 (replica is busy)");
+
+        let error = run_hook(Hook::PostBackup, "sleep 5", &env, Duration::from_millis(100)).await.unwrap_err();
+        assert_eq!(error.to_string(), "post_backup hook did not finish within 100ms.");
+    }
+}
@@ -0,0 +1,188 @@
+use std::sync::Arc;
+use axum::extract::{Path, Query, Request, State};
+use axum::http::{header, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use log::{error, info};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use crate::http::HttpState;
+use crate::service::mysql::database::RunRow;
+
+/// A failed request, answered as `{"error": "..."}`.
+pub struct ApiError(StatusCode, String);
+
+impl ApiError {
+    fn not_found(message: String) -> ApiError {
+        ApiError(StatusCode::NOT_FOUND, message)
+    }
+}
+
+impl From<sqlx::Error> for ApiError {
+    fn from(error: sqlx::Error) -> ApiError {
+        error!("Catalog query failed, error: {}", error);
+        ApiError(StatusCode::INTERNAL_SERVER_ERROR, "The catalog could not be queried.".to_string())
+    }
+}
+
+#[derive(Serialize)]
+struct ErrorBody {
+    error: String
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        (self.0, Json(ErrorBody { error: self.1 })).into_response()
+    }
+}
+
+/// Rejects requests without the configured bearer token.
+pub async fn authenticate(State(state): State<Arc<HttpState>>, request: Request, next: Next) -> Response {
+    let token = request.headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+    match (token, &state.api_token) {
+        (Some(token), Some(expected)) if constant_time_eq(token.as_bytes(), expected.as_bytes()) => next.run(request).await,
+        _ => ApiError(StatusCode::UNAUTHORIZED, "A valid bearer token is required.".to_string()).into_response()
+    }
+}
+
+/// Compares without stopping at the first difference, so the time taken does not leak how much of a token matched.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |difference, (a, b)| difference | (a ^ b)) == 0
+}
+
+#[derive(Serialize)]
+pub struct ServiceStatus {
+    name: String,
+    jobs: Vec<String>,
+    running: bool,
+    last_run: Option<RunRow>
+}
+
+pub async fn list_services(State(state): State<Arc<HttpState>>) -> Result<Json<Vec<ServiceStatus>>, ApiError> {
+    let mut services = vec![];
+    for service in &state.services {
+        services.push(ServiceStatus {
+            name: service.name().to_string(),
+            jobs: service.jobs(),
+            running: service.is_running().await,
+            last_run: state.catalog.find_last_run(service.name(), None, None).await?
+        });
+    }
+    services.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(Json(services))
+}
+
+#[derive(Serialize)]
+pub struct RunStarted {
+    run: Uuid
+}
+
+#[derive(Deserialize)]
+pub struct RunQuery {
+    job: Option<String>
+}
+
+pub async fn run_service(State(state): State<Arc<HttpState>>, Path(name): Path<String>, Query(query): Query<RunQuery>) -> Result<(StatusCode, Json<RunStarted>), ApiError> {
+    let Some(service) = state.services.iter().find(|service| service.name() == name) else {
+        return Err(ApiError::not_found(format!("Service {} is not configured.", name)));
+    };
+    let jobs = service.jobs();
+    match &query.job {
+        Some(job) if !jobs.contains(job) => return Err(ApiError::not_found(format!("Service {} has no backup job {}.", name, job))),
+        None if !jobs.is_empty() => return Err(ApiError(StatusCode::BAD_REQUEST, format!("Service {} has several backup jobs, pick one of: {}.", name, jobs.join(", ")))),
+        _ => {}
+    }
+
+    // Goes through the same running lock as the scheduled jobs.
+    match service.clone().trigger(query.job.as_deref()).await.map_err(|error| error.to_string()) {
+        Ok(Some(run)) => {
+            info!("Backup of {} requested through the API, run: {}", name, run);
+            Ok((StatusCode::ACCEPTED, Json(RunStarted { run })))
+        }
+        Ok(None) => Err(ApiError(StatusCode::CONFLICT, format!("A backup of {} is already running.", name))),
+        Err(error) => {
+            error!("Failed to start a backup of {}, error: {}", name, error);
+            Err(ApiError(StatusCode::INTERNAL_SERVER_ERROR, error))
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct BackupsQuery {
+    service: Option<String>
+}
+
+pub async fn list_backups(State(state): State<Arc<HttpState>>, Query(query): Query<BackupsQuery>) -> Result<Response, ApiError> {
+    Ok(Json(state.catalog.find_backups(query.service.as_deref()).await?).into_response())
+}
+
+pub async fn get_backup(State(state): State<Arc<HttpState>>, Path(uuid): Path<Uuid>) -> Result<Response, ApiError> {
+    match state.catalog.find_backup(uuid).await? {
+        Some(backup) => Ok(Json(backup).into_response()),
+        None => Err(ApiError::not_found(format!("Backup {} was not found in the catalog.", uuid)))
+    }
+}
+
+pub async fn get_run(State(state): State<Arc<HttpState>>, Path(uuid): Path<Uuid>) -> Result<Response, ApiError> {
+    match state.catalog.find_run(uuid).await? {
+        Some(run) => Ok(Json(run).into_response()),
+        None => Err(ApiError::not_found(format!("Run {} was not found in the catalog.", uuid)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::{to_bytes, Body};
+    use axum::http::Request;
+    use tempfile::tempdir;
+    use tower::ServiceExt;
+    use crate::catalog::sqlite::SqliteCatalog;
+    use crate::config::CatalogConfig;
+    use crate::http::router;
+
+    async fn state(directory: &std::path::Path) -> Arc<HttpState> {
+        let catalog = SqliteCatalog::connect(&CatalogConfig::default(), directory).await.unwrap();
+        Arc::new(HttpState {
+            services: vec![],
+            catalog: Box::leak(Box::new(catalog)),
+            api_token: Some("secret".to_string())
+        })
+    }
+
+    fn get(uri: &str, token: Option<&str>) -> Request<Body> {
+        let mut request = Request::get(uri);
+        if let Some(token) = token {
+            request = request.header(header::AUTHORIZATION, format!("Bearer {}", token));
+        }
+        request.body(Body::empty()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_requires_token() {
+        let dir = tempdir().unwrap();
+        let app = router(state(dir.path()).await);
+        assert_eq!(app.clone().oneshot(get("/backups", None)).await.unwrap().status(), StatusCode::UNAUTHORIZED);
+        assert_eq!(app.clone().oneshot(get("/backups", Some("wrong"))).await.unwrap().status(), StatusCode::UNAUTHORIZED);
+        assert_eq!(app.clone().oneshot(get("/backups", Some("secret"))).await.unwrap().status(), StatusCode::OK);
+        // Metrics stay open for the scraper.
+        assert_eq!(app.oneshot(get("/metrics", None)).await.unwrap().status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_not_found() {
+        let dir = tempdir().unwrap();
+        let app = router(state(dir.path()).await);
+        let response = app.clone().oneshot(get(&format!("/backups/{}", Uuid::from_u128(1)), Some("secret"))).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        assert!(String::from_utf8_lossy(&body).starts_with("{\"error\":\"Backup 00000000-0000-0000-0000-000000000001 was not found"));
+
+        let request = Request::post("/services/unknown/run").header(header::AUTHORIZATION, "Bearer secret").body(Body::empty()).unwrap();
+        assert_eq!(app.oneshot(request).await.unwrap().status(), StatusCode::NOT_FOUND);
+    }
+}
@@ -0,0 +1,152 @@
+use std::fmt::Write;
+use std::sync::Arc;
+use axum::extract::State;
+use axum::http::{header, StatusCode};
+use axum::response::{IntoResponse, Response};
+use log::error;
+use sqlx::types::chrono::NaiveDateTime;
+use crate::catalog::Catalog;
+use crate::http::HttpState;
+use crate::service::service::Service;
+
+/// What the exposition format is served as.
+const CONTENT_TYPE: &str = "text/plain; version=0.0.4; charset=utf-8";
+
+/// A service's numbers, taken from the catalog at scrape time so they survive restarts.
+#[derive(Debug, Default)]
+struct ServiceMetrics {
+    service: String,
+    running: bool,
+    last_success: Option<NaiveDateTime>,
+    last_run_success: Option<bool>,
+    last_run_duration: Option<f64>,
+    last_backup_size: Option<i64>,
+    runs: i64,
+    failures: i64,
+    catalog_backups: usize,
+    catalog_bytes: i64
+}
+
+pub async fn metrics(State(state): State<Arc<HttpState>>) -> Response {
+    match render(&state.services, state.catalog).await {
+        Ok(body) => ([(header::CONTENT_TYPE, CONTENT_TYPE)], body).into_response(),
+        Err(error) => {
+            error!("Failed to collect metrics, error: {}", error);
+            (StatusCode::INTERNAL_SERVER_ERROR, error.to_string()).into_response()
+        }
+    }
+}
+
+async fn collect(service: &dyn Service, catalog: &dyn Catalog) -> Result<ServiceMetrics, sqlx::Error> {
+    let name = service.name();
+    let last_run = catalog.find_last_run(name, None, None).await?;
+    let last_success = catalog.find_last_run(name, None, Some("succeeded")).await?;
+    let counts = catalog.count_runs(name).await?;
+    let backups = catalog.find_local_backups(name).await?;
+
+    // A mysqldump run writes one artifact per database, so the size of a run is the sum of what it wrote.
+    let last_backup_size = match &last_success {
+        Some(run) if backups.iter().any(|backup| backup.run_uuid == Some(run.uuid)) => {
+            Some(backups.iter().filter(|backup| backup.run_uuid == Some(run.uuid)).map(|backup| backup.size).sum())
+        }
+        _ => backups.last().map(|backup| backup.size)
+    };
+
+    Ok(ServiceMetrics {
+        service: name.to_string(),
+        running: service.is_running().await,
+        last_success: last_success.and_then(|run| run.finished_at),
+        last_run_success: last_run.as_ref().map(|run| run.status == "succeeded"),
+        last_run_duration: last_run.and_then(|run| run.finished_at.map(|finished_at| (finished_at - run.started_at).num_milliseconds() as f64 / 1000.0)),
+        last_backup_size,
+        runs: counts.iter().map(|(_, count)| count).sum(),
+        failures: counts.iter().filter(|(status, _)| status == "failed").map(|(_, count)| count).sum(),
+        catalog_backups: backups.len(),
+        catalog_bytes: backups.iter().map(|backup| backup.size).sum()
+    })
+}
+
+/// Renders every service's metrics in the Prometheus text exposition format.
+pub async fn render(services: &[Arc<dyn Service>], catalog: &dyn Catalog) -> Result<String, sqlx::Error> {
+    let mut collected = vec![];
+    for service in services {
+        collected.push(collect(service.as_ref(), catalog).await?);
+    }
+    collected.sort_by(|a, b| a.service.cmp(&b.service));
+    Ok(format_metrics(&collected))
+}
+
+fn escape_label(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+fn format_metrics(services: &[ServiceMetrics]) -> String {
+    type Value = fn(&ServiceMetrics) -> Option<f64>;
+    let families: [(&str, &str, &str, Value); 9] = [
+        // Never having succeeded reports 0, so "no success in N hours" alerts fire for new services too.
+        ("mysql_backup_last_success_timestamp_seconds", "gauge", "Unix time the last successful run finished, 0 if there was none.",
+            |metrics| Some(metrics.last_success.map(|at| at.and_utc().timestamp_millis() as f64 / 1000.0).unwrap_or(0.0))),
+        ("mysql_backup_last_run_success", "gauge", "Whether the last finished run succeeded.",
+            |metrics| metrics.last_run_success.map(|success| if success { 1.0 } else { 0.0 })),
+        ("mysql_backup_last_run_duration_seconds", "gauge", "How long the last finished run took.",
+            |metrics| metrics.last_run_duration),
+        ("mysql_backup_last_size_bytes", "gauge", "Size of the artifacts written by the last successful run.",
+            |metrics| metrics.last_backup_size.map(|size| size as f64)),
+        ("mysql_backup_runs_total", "counter", "Finished and running backup runs.",
+            |metrics| Some(metrics.runs as f64)),
+        ("mysql_backup_failures_total", "counter", "Backup runs which failed.",
+            |metrics| Some(metrics.failures as f64)),
+        ("mysql_backup_catalog_backups", "gauge", "Backups of the service in the catalog.",
+            |metrics| Some(metrics.catalog_backups as f64)),
+        ("mysql_backup_catalog_bytes", "gauge", "Total size of the backups of the service in the catalog.",
+            |metrics| Some(metrics.catalog_bytes as f64)),
+        ("mysql_backup_running", "gauge", "Whether a backup is being taken right now.",
+            |metrics| Some(if metrics.running { 1.0 } else { 0.0 })),
+    ];
+
+    let mut output = String::new();
+    for (name, kind, help, value) in families {
+        writeln!(output, "# HELP {} {}", name, help).unwrap();
+        writeln!(output, "# TYPE {} {}", name, kind).unwrap();
+        for metrics in services {
+            if let Some(value) = value(metrics) {
+                writeln!(output, "{}{{service=\"{}\"}} {}", name, escape_label(&metrics.service), value).unwrap();
+            }
+        }
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sqlx::types::chrono::NaiveDate;
+
+    #[test]
+    fn test_format_metrics() {
+        let services = [
+            ServiceMetrics {
+                service: "mysql-r1".to_string(),
+                running: true,
+                last_success: Some(NaiveDate::from_ymd_opt(2024, 4, 16).unwrap().and_hms_milli_opt(18, 0, 0, 500).unwrap()),
+                last_run_success: Some(true),
+                last_run_duration: Some(12.5),
+                last_backup_size: Some(2048),
+                runs: 3,
+                failures: 1,
+                catalog_backups: 2,
+                catalog_bytes: 4096
+            },
+            ServiceMetrics { service: "new\"one".to_string(), ..Default::default() }
+        ];
+        let output = format_metrics(&services);
+        assert!(output.contains("# TYPE mysql_backup_runs_total counter\n"));
+        assert!(output.contains("mysql_backup_last_success_timestamp_seconds{service=\"mysql-r1\"} 1713290400.5\n"));
+        assert!(output.contains("mysql_backup_last_success_timestamp_seconds{service=\"new\\\"one\"} 0\n"));
+        assert!(output.contains("mysql_backup_last_run_duration_seconds{service=\"mysql-r1\"} 12.5\n"));
+        assert!(output.contains("mysql_backup_failures_total{service=\"mysql-r1\"} 1\n"));
+        assert!(output.contains("mysql_backup_running{service=\"mysql-r1\"} 1\n"));
+        // Services which never ran have no last run to report on.
+        assert!(!output.contains("mysql_backup_last_run_success{service=\"new\\\"one\"}"));
+    }
+}
@@ -0,0 +1,47 @@
+pub mod api;
+pub mod metrics;
+
+use std::sync::Arc;
+use axum::Router;
+use axum::middleware::from_fn_with_state;
+use axum::routing::{get, post};
+use log::{error, info};
+use tokio::net::TcpListener;
+use tokio::task::JoinHandle;
+use crate::catalog::Catalog;
+use crate::config::HttpConfig;
+use crate::service::service::Service;
+
+/// What the handlers get to work with.
+pub struct HttpState {
+    pub services: Vec<Arc<dyn Service>>,
+    pub catalog: &'static dyn Catalog,
+    pub api_token: Option<String>
+}
+
+pub fn router(state: Arc<HttpState>) -> Router {
+    let mut router = Router::new().route("/metrics", get(metrics::metrics));
+    if state.api_token.is_some() {
+        let api = Router::new()
+            .route("/services", get(api::list_services))
+            .route("/services/{name}/run", post(api::run_service))
+            .route("/backups", get(api::list_backups))
+            .route("/backups/{uuid}", get(api::get_backup))
+            .route("/runs/{uuid}", get(api::get_run))
+            .route_layer(from_fn_with_state(state.clone(), api::authenticate));
+        router = router.merge(api);
+    }
+    router.with_state(state)
+}
+
+/// Starts serving in the background, the listener is bound first so a taken address fails right away.
+pub async fn serve(config: &HttpConfig, state: HttpState) -> Result<JoinHandle<()>, Box<dyn std::error::Error>> {
+    let listener = TcpListener::bind(&config.listen).await?;
+    info!("Listening on http://{}", listener.local_addr()?);
+    let app = router(Arc::new(state));
+    Ok(tokio::spawn(async move {
+        if let Err(error) = axum::serve(listener, app).await {
+            error!("HTTP server stopped, error: {}", error);
+        }
+    }))
+}
@@ -1,96 +1,502 @@
 use std::env;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
 use std::sync::Arc;
-use log::{error, info};
-use sqlx::{Pool, Sqlite};
-use sqlx::sqlite::SqlitePoolOptions;
+use clap::Parser;
+use log::{error, info, warn};
 use tokio_cron_scheduler::JobScheduler;
+use crate::cli::{Cli, Commands, ConfigCommands, DeleteArgs, ExitStatus, ListArgs, PruneArgs, RestoreArgs, RunArgs, ShowArgs, VerifyArgs};
 use crate::config::*;
+use crate::service::mysql::config::{MySQLBackupConfig, MySQLBackupType, MySQLConnectionConfig};
+use crate::service::mysql::binlog::BinlogArchiver;
+use crate::catalog::Catalog;
+use crate::http::HttpState;
+use crate::service::mysql::database::{MysqlBackupRow, RunTrigger};
 use crate::service::mysql::mysql_service::MySQLService;
+use crate::service::mysql::restore::{MySqlRestoreRunner, RestoreOptions};
+use crate::service::mysql::verify::MySqlVerifyRunner;
+use crate::service::mysql::xtrabackup::XtraBackupMode;
 use crate::service::service::{ServiceScheduler, Service};
 use tokio::signal::ctrl_c;
 #[cfg(unix)]
 use tokio::signal::unix::{signal, SignalKind};
 use tokio::sync::OnceCell;
+use uuid::Uuid;
+use crate::utils::format_size;
 
+mod artifact;
+mod catalog;
+mod checksum;
+mod cli;
+mod compression;
 mod config;
+mod encryption;
+mod hook;
+mod http;
+mod notification;
+mod retention;
 mod service;
+mod storage;
 mod utils;
+mod watchdog;
 
-const DB_URL: &str = "sqlite://sqlite.db?mode=rwc";
-static DB_POOL: OnceCell<Pool<Sqlite>> = OnceCell::const_new();
+static CATALOG: OnceCell<Box<dyn Catalog>> = OnceCell::const_new();
 
 #[tokio::main]
-async fn main() -> Result<(), i32> {
+async fn main() -> ExitCode {
     env::set_var("RUST_LOG", "debug");
     env_logger::init();
+    let cli = Cli::parse();
 
-    // Fetch the current path
-    let current_path = if env::var("RUST_ENV") == Ok("production".to_string()) {
+    match run(cli).await {
+        Ok(_) => ExitCode::SUCCESS,
+        Err(status) => status.into()
+    }
+}
+
+/// The directory we are installed in, where the configuration and catalog live unless told otherwise.
+fn default_data_dir() -> Result<PathBuf, ExitStatus> {
+    if env::var("RUST_ENV") == Ok("production".to_string()) {
         match env::current_exe() {
-            Ok(path) => path,
+            Ok(path) => Ok(path.parent().map(|parent| parent.to_path_buf()).unwrap_or(path)),
             Err(error) => {
                 error!("Failed to fetch path. Error: {}", error);
-                return Err(-1)
+                Err(ExitStatus::Failure)
             }
         }
     } else {
-        let create_dir = match env::var("CARGO_MANIFEST_DIR") {
-            Ok(path) => path,
+        match env::var("CARGO_MANIFEST_DIR") {
+            Ok(path) => Ok(PathBuf::from(path)),
             Err(error) => {
-                error!("Failed ot fetch manifest dir. Error: {}", error);
-                return Err(-1)
+                error!("Failed to fetch manifest dir, pass --data-dir instead. Error: {}", error);
+                Err(ExitStatus::Failure)
             }
-        };
-        Path::new(&create_dir).to_path_buf()
-    };
+        }
+    }
+}
+
+async fn run(cli: Cli) -> Result<(), ExitStatus> {
+    // Only look for the default directory when it is needed, given paths always work.
+    let data_dir = || cli.data_dir.clone().map(Ok).unwrap_or_else(default_data_dir);
 
     // Read the configuration
-    let config_path = current_path.clone().join("config.toml");
+    let config_path = match &cli.config {
+        Some(config_path) => config_path.clone(),
+        None => data_dir()?.join("config.toml")
+    };
     let config = match Config::new(config_path.to_str().unwrap()).await {
         Ok(config) => config,
         Err(error) => {
-            error!("An error occurred while parsing config: {}", error);
-            return Err(-1)
+            error!("An error occurred while parsing config {}: {}", config_path.display(), error);
+            return Err(ExitStatus::Config)
         }
     };
 
-    // Create a new pool
-    let pool = match DB_POOL.get_or_try_init(|| async {
-        SqlitePoolOptions::new().max_connections(15).connect(DB_URL).await
-    }).await {
-        Ok(pool) => pool,
+    // Checking the configuration does not need the catalog.
+    let command = cli.command.unwrap_or(Commands::Daemon);
+    if let Commands::Config(ConfigCommands::Check) = command {
+        return check_config(&config, &config_path);
+    }
+
+    // Open the catalog, bringing its schema up to date
+    let data_dir = data_dir()?;
+    let catalog_config = config.catalog.clone().unwrap_or_default();
+    let catalog = match CATALOG.get_or_try_init(|| catalog::connect(&catalog_config, &data_dir)).await {
+        Ok(catalog) => catalog.as_ref(),
         Err(error) => {
-            error!("An error occurred while connecting to the database: {}", error);
-            return Err(-1)
+            error!("An error occurred while opening the catalog: {}", error);
+            return Err(ExitStatus::Catalog)
         }
     };
+    adopt_backups(&config, catalog).await?;
+
+    match command {
+        Commands::Daemon => daemon(config, catalog).await,
+        Commands::Run(args) => run_backup(config, args).await,
+        Commands::List(args) => list(catalog, args).await,
+        Commands::Show(args) => show(catalog, args).await,
+        Commands::Prune(args) => prune(config, catalog, args).await,
+        Commands::Restore(args) => restore(config, catalog, args).await,
+        Commands::Delete(args) => delete(config, catalog, args).await,
+        Commands::Verify(args) => verify(config, catalog, args).await,
+        Commands::Config(ConfigCommands::Check) => unreachable!()
+    }
+}
 
-    // Migrate the database
-    let migrations_path = current_path.clone().join("migrations");
-    let result = match sqlx::migrate::Migrator::new(migrations_path).await {
-        Ok(m) => m.run(pool).await,
+/// Backups recorded before the catalog tracked services are never pruned, with a single service they can only be its own.
+async fn adopt_backups(config: &Config, catalog: &dyn Catalog) -> Result<(), ExitStatus> {
+    let mut service_names = config.services.keys();
+    let (Some(service_name), None) = (service_names.next(), service_names.next()) else { return Ok(()) };
+    match catalog.adopt_backups(service_name).await {
+        Ok(0) => Ok(()),
+        Ok(count) => {
+            info!("Assigned {} backup(s) recorded without a service to {}.", count, service_name);
+            Ok(())
+        }
         Err(error) => {
-            error!("An error occurred while initializing migrations: {}", error);
-            return Err(-1)
+            error!("An error occurred while assigning backups to {}: {}", service_name, error);
+            Err(ExitStatus::Catalog)
+        }
+    }
+}
+
+fn check_config(config: &Config, config_path: &Path) -> Result<(), ExitStatus> {
+    let problems = config.check();
+    if !problems.is_empty() {
+        for problem in &problems {
+            error!("{}", problem);
+        }
+        error!("{} has {} problem(s).", config_path.display(), problems.len());
+        return Err(ExitStatus::Config)
+    }
+    info!("{} is valid, {} service(s) configured.", config_path.display(), config.services.len());
+    Ok(())
+}
+
+/// The configured MySQL service with the given name.
+fn configured_service(config: &Config, service_name: &str) -> Result<MySQLService, ExitStatus> {
+    match config.services.get(service_name) {
+        Some(ServiceConfigEnum::MySQL(mysql_config)) => Ok(MySQLService::new(service_name, mysql_config.clone(), config.backup.clone(), config.notifications.clone())),
+        None => {
+            error!("Service {} is not configured.", service_name);
+            Err(ExitStatus::NotFound)
+        }
+    }
+}
+
+async fn find_backup(catalog: &dyn Catalog, uuid: Uuid) -> Result<MysqlBackupRow, ExitStatus> {
+    match catalog.find_backup(uuid).await {
+        Ok(Some(backup)) => Ok(backup),
+        Ok(None) => {
+            error!("Backup {} was not found in the catalog.", uuid);
+            Err(ExitStatus::NotFound)
+        }
+        Err(error) => {
+            error!("An error occurred while looking up the backup: {}", error);
+            Err(ExitStatus::Catalog)
+        }
+    }
+}
+
+async fn run_backup(config: Config, args: RunArgs) -> Result<(), ExitStatus> {
+    let mysql_service = match configured_service(&config, &args.service)?.find_job(args.job.as_deref()) {
+        Ok(mysql_service) => mysql_service,
+        Err(error) => {
+            error!("{}", error);
+            return Err(if args.job.is_some() { ExitStatus::NotFound } else { ExitStatus::Config })
+        }
+    };
+    let mode = match &mysql_service.backup {
+        Some(MySQLBackupConfig { backup_type: MySQLBackupType::XtraBackup(_), .. }) if args.full => Some(XtraBackupMode::Full),
+        Some(MySQLBackupConfig { backup_type: MySQLBackupType::XtraBackup(_), .. }) if args.incremental => Some(XtraBackupMode::Incremental),
+        Some(_) if args.full || args.incremental => {
+            error!("--full and --incremental only apply to xtrabackup services.");
+            return Err(ExitStatus::Config)
         }
+        _ => None
     };
 
-    // See if migration was successful.
+    if !mysql_service.try_set_running().await {
+        error!("A backup of {} is already running.", mysql_service.label());
+        return Err(ExitStatus::Busy)
+    }
+    info!("Running backup for MySQL service: {}", mysql_service.label());
+    let previous = mysql_service.last_run().await;
+    let result = mysql_service.execute_run(RunTrigger::Manual, mode).await.map_err(|error| error.to_string());
+    mysql_service.notify_run(previous, result.clone().err()).await;
+    mysql_service.set_running(false).await;
+
     match result {
-        Ok(_) => (),
+        Ok(_) => {
+            info!("Backup completed!");
+            Ok(())
+        }
+        Err(error) => {
+            error!("Failed to run backup for MySQL service: {}, error: {}", mysql_service.label(), error);
+            Err(ExitStatus::Failure)
+        }
+    }
+}
+
+async fn list(catalog: &dyn Catalog, args: ListArgs) -> Result<(), ExitStatus> {
+    let backups = match catalog.find_backups(args.service.as_deref()).await {
+        Ok(backups) => backups,
         Err(error) => {
-            error!("An error occurred while migrating the database: {}", error);
-            return Err(-1)
+            error!("An error occurred while looking up backups: {}", error);
+            return Err(ExitStatus::Catalog)
+        }
+    };
+
+    println!("{:<36}  {:<16}  {:<11}  {:<19}  {:>10}  {:<8}  DATABASE", "UUID", "SERVICE", "TYPE", "CREATED (UTC)", "SIZE", "UPLOADED");
+    for backup in &backups {
+        println!(
+            "{:<36}  {:<16}  {:<11}  {:<19}  {:>10}  {:<8}  {}",
+            backup.uuid,
+            backup.service.as_deref().unwrap_or("-"),
+            backup.kind(),
+            backup.created_at.format("%Y-%m-%d %H:%M:%S"),
+            format_size(backup.size as u64),
+            if backup.remote_key.is_some() { "yes" } else { "no" },
+            backup.database_name.as_deref().unwrap_or("-")
+        );
+    }
+    Ok(())
+}
+
+async fn show(catalog: &dyn Catalog, args: ShowArgs) -> Result<(), ExitStatus> {
+    let backup = find_backup(catalog, args.uuid).await?;
+    let run = match backup.run_uuid {
+        Some(run_uuid) => match catalog.find_run(run_uuid).await {
+            Ok(run) => run,
+            Err(error) => {
+                error!("An error occurred while looking up the run: {}", error);
+                return Err(ExitStatus::Catalog)
+            }
+        },
+        None => None
+    };
+
+    let optional = |value: Option<String>| value.unwrap_or("-".to_string());
+    println!("UUID:          {}", backup.uuid);
+    println!("Service:       {}", optional(backup.service.clone()));
+    println!("Job:           {}", optional(backup.job.clone()));
+    println!("Host:          {}", optional(backup.host.clone()));
+    println!("Type:          {}", backup.kind());
+    println!("Created (UTC): {}", backup.created_at.format("%Y-%m-%d %H:%M:%S"));
+    println!("Path:          {}", backup.path);
+    println!("Size:          {} ({} bytes)", format_size(backup.size as u64), backup.size);
+    println!("Database:      {}", optional(backup.database_name.clone()));
+    println!("Base:          {}", optional(backup.base_uuid.map(|uuid| uuid.to_string())));
+    if let (Some(from_lsn), Some(to_lsn)) = (backup.from_lsn, backup.to_lsn) {
+        println!("LSN:           {} - {}", from_lsn, to_lsn);
+    }
+    if let Some(binlog_file) = &backup.binlog_file {
+        println!("Binary log:    {}:{}", binlog_file, optional(backup.binlog_position.map(|position| position.to_string())));
+    }
+    println!("GTID executed: {}", optional(backup.gtid_executed.clone()));
+    println!("Compression:   {}", optional(backup.compression.clone()));
+    println!("Encrypted to:  {}", optional(backup.encryption_recipients.clone()));
+    println!("Uploaded to:   {}", optional(backup.remote_key.clone()));
+    println!("SHA-256:       {}", optional(backup.checksum.clone()));
+    if let Some(run) = run {
+        println!("Run:           {} ({}, {})", run.uuid, run.trigger, run.status);
+        println!("Run started:   {}", run.started_at.format("%Y-%m-%d %H:%M:%S"));
+        println!("Run finished:  {}", optional(run.finished_at.map(|finished_at| finished_at.format("%Y-%m-%d %H:%M:%S").to_string())));
+        if let Some(error) = run.error {
+            println!("Run error:     {}", error);
         }
+    }
+    Ok(())
+}
+
+async fn prune(config: Config, catalog: &dyn Catalog, args: PruneArgs) -> Result<(), ExitStatus> {
+    let service_names = match &args.service {
+        Some(service_name) => vec![service_name.clone()],
+        None => config.services.keys().cloned().collect()
     };
 
+    let mut failed = false;
+    for service_name in service_names {
+        let mysql_service = configured_service(&config, &service_name)?;
+        let expired = match mysql_service.expired_backups(catalog).await {
+            Ok(expired) => expired,
+            Err(error) => {
+                error!("Failed to apply the retention policy of {}. Error: {}", service_name, error);
+                failed = true;
+                continue;
+            }
+        };
+
+        if args.dry_run {
+            for backup in &expired {
+                info!("Would remove backup {} of {} taken at {}: {}", backup.uuid, service_name, backup.created_at, backup.path);
+            }
+            info!("{} backup(s) of {} would be removed.", expired.len(), service_name);
+        } else if let Err(error) = mysql_service.remove_backups(catalog, &expired).await {
+            error!("Failed to prune backups of {}. Error: {}", service_name, error);
+            failed = true;
+        } else {
+            info!("Removed {} backup(s) of {}.", expired.len(), service_name);
+        }
+    }
+
+    if failed {
+        return Err(ExitStatus::Failure)
+    }
+    Ok(())
+}
+
+/// The service which took the backup, falling back to the global settings once it is no longer configured.
+fn service_for_backup(config: &Config, backup: &MysqlBackupRow) -> MySQLService {
+    let service_name = backup.service.clone().unwrap_or_default();
+    let service_config = match config.services.get(&service_name) {
+        Some(ServiceConfigEnum::MySQL(mysql_config)) => mysql_config.clone(),
+        None => MySQLConnectionConfig::default()
+    };
+    MySQLService::new(&service_name, service_config, config.backup.clone(), config.notifications.clone()).for_backup(backup)
+}
+
+async fn verify(config: Config, catalog: &dyn Catalog, args: VerifyArgs) -> Result<(), ExitStatus> {
+    let backups = match args.uuid {
+        Some(uuid) => catalog.find_backup(uuid).await.map(|backup| backup.into_iter().collect::<Vec<_>>()),
+        None => catalog.find_backups(args.service.as_deref()).await
+    };
+    let backups = match backups {
+        Ok(backups) if backups.is_empty() => {
+            error!("No backups found to verify.");
+            return Err(ExitStatus::NotFound)
+        }
+        Ok(backups) => backups,
+        Err(error) => {
+            error!("An error occurred while looking up backups: {}", error);
+            return Err(ExitStatus::Catalog)
+        }
+    };
+
+    let mut failed = 0;
+    for backup in &backups {
+        let mysql_service = service_for_backup(&config, backup);
+        match mysql_service.verify_backup(backup, args.remote).await {
+            Ok(mismatches) if mismatches.is_empty() => info!("Backup {} is intact.", backup.uuid),
+            Ok(mismatches) => {
+                failed += 1;
+                for mismatch in mismatches {
+                    error!("Backup {} is corrupt: {}", backup.uuid, mismatch);
+                }
+            }
+            Err(error) => {
+                failed += 1;
+                error!("Unable to verify backup {}. Error: {}", backup.uuid, error);
+            }
+        }
+    }
+
+    if failed > 0 {
+        error!("{} of {} backup(s) failed verification.", failed, backups.len());
+        return Err(ExitStatus::Corrupt)
+    }
+    info!("Verified {} backup(s).", backups.len());
+    Ok(())
+}
+
+async fn delete(config: Config, catalog: &dyn Catalog, args: DeleteArgs) -> Result<(), ExitStatus> {
+    let backup = find_backup(catalog, args.uuid).await?;
+
+    // Deleting a base out from under its incrementals leaves them unrestorable.
+    let dependents = match MysqlBackupRow::find_dependents(catalog, backup.uuid).await {
+        Ok(dependents) => dependents,
+        Err(error) => {
+            error!("An error occurred while looking up dependent backups: {}", error);
+            return Err(ExitStatus::Catalog)
+        }
+    };
+    let mut backups = vec![];
+    if !dependents.is_empty() {
+        let uuids = dependents.iter().map(|dependent| dependent.uuid.to_string()).collect::<Vec<String>>().join(", ");
+        if args.with_dependents {
+            info!("Also deleting dependent backup(s): {}", uuids);
+            backups.extend(dependents);
+        } else if args.force {
+            warn!("Deleting {} orphans dependent backup(s): {}", backup.uuid, uuids);
+        } else {
+            error!("Backup {} has dependent backup(s): {}. Use --with-dependents to delete them too, or --force to orphan them.", backup.uuid, uuids);
+            return Err(ExitStatus::Failure)
+        }
+    }
+
+    // The service that took the backup knows where its uploaded copies live.
+    let mysql_service = service_for_backup(&config, &backup);
+    backups.push(backup);
+
+    match mysql_service.remove_backups(catalog, &backups).await {
+        Ok(_) => Ok(()),
+        Err(error) => {
+            error!("Failed to delete backup {}. Error: {}", args.uuid, error);
+            Err(ExitStatus::Failure)
+        }
+    }
+}
+
+async fn restore(config: Config, catalog: &dyn Catalog, args: RestoreArgs) -> Result<(), ExitStatus> {
+    let backup = find_backup(catalog, args.uuid).await?;
+
+    // Backups know which service took them, so the service only has to be given to restore somewhere else.
+    let service = args.service.clone().or(backup.service.clone());
+
+    // Figure out the target server, an explicit defaults file always wins over the service connection.
+    let service_config = match &service {
+        Some(service_name) => match config.services.get(service_name) {
+            Some(ServiceConfigEnum::MySQL(mysql_config)) => mysql_config.clone(),
+            None => {
+                error!("Service {} is not configured.", service_name);
+                return Err(ExitStatus::NotFound)
+            }
+        },
+        None => MySQLConnectionConfig::default()
+    };
+    let connection_config = if let Some(defaults_file) = args.defaults_file {
+        MySQLConnectionConfig {
+            defaults_file: Some(defaults_file),
+            backup: service_config.backup,
+            binlog: service_config.binlog,
+            ..Default::default()
+        }
+    } else if service.is_none() && (backup.backup_type == 0 || args.binlogs_only) {
+        error!("Restoring into a server requires either --service or --defaults-file.");
+        return Err(ExitStatus::Config)
+    } else {
+        // Preparing an xtrabackup chain does not talk to a server.
+        service_config
+    };
+
+    // Binary logs are archived per service, so rolling forward needs to know which one.
+    let roll_forward = args.until.is_some() || args.until_gtid.is_some();
+    if (roll_forward || args.binlogs_only) && service.is_none() {
+        error!("Rolling forward with binary logs requires --service.");
+        return Err(ExitStatus::Config)
+    }
+
+    let mysql_service = MySQLService::new(&service.unwrap_or_default(), connection_config, config.backup, config.notifications).for_backup(&backup);
+    let options = RestoreOptions {
+        target_database: args.database,
+        all_tables: args.all_tables,
+        staging_dir: args.staging_dir,
+        copy_back: args.copy_back,
+        until: args.until,
+        until_gtid: args.until_gtid,
+        identity_file: args.identity_file,
+    };
+    let result = match backup.backup_type {
+        _ if args.binlogs_only => Ok(()),
+        0 => mysql_service.restore_mysqldump(&backup, &options).await,
+        _ => mysql_service.restore_xtrabackup(catalog, &backup, &options).await.map(|_| ())
+    };
+    if let Err(error) = result {
+        error!("Failed to restore backup {}. Error: {}", backup.uuid, error);
+        return Err(ExitStatus::Failure)
+    }
+
+    // A prepared xtrabackup has to be started by hand before binary logs can be replayed on top of it.
+    if roll_forward || args.binlogs_only {
+        if backup.backup_type == 1 && !args.binlogs_only {
+            info!("Start MySQL on the restored datadir and run the restore again with --binlogs-only to roll forward.");
+        } else if let Err(error) = mysql_service.replay_binlogs(catalog, &backup, &options).await {
+            error!("Failed to roll forward backup {}. Error: {}", backup.uuid, error);
+            return Err(ExitStatus::Failure)
+        }
+    }
+
+    info!("Restore of {} completed!", backup.uuid);
+    Ok(())
+}
+
+async fn daemon(config: Config, catalog: &'static dyn Catalog) -> Result<(), ExitStatus> {
     // Now we simply iterate all services and start handling them.
     let mut sched = match JobScheduler::new().await {
         Ok(scheduler) => scheduler,
         Err(error) => {
             error!("An error occurred while creating scheduler: {}", error);
-            return Err(-1)
+            return Err(ExitStatus::Failure)
         }
     };
     sched.set_shutdown_handler(Box::new(|| {
@@ -101,30 +507,43 @@ async fn main() -> Result<(), i32> {
 
     // Schedule the service.
     let mut services: Vec<Arc<dyn Service>> = vec![];
+    let mut mysql_services = vec![];
     for (service_name, service_config) in config.services {
         info!("Scheduling {}", service_name);
 
         match service_config {
             ServiceConfigEnum::MySQL(mysql_config) => {
-                let mysql_service = Arc::new(MySQLService::new(mysql_config, config.backup.clone()));
+                let mysql_service = Arc::new(MySQLService::new(&service_name, mysql_config, config.backup.clone(), config.notifications.clone()));
                 match MySQLService::schedule(mysql_service.clone(), &mut sched, &service_name).await {
                     Ok(_) => (),
                     Err(error) => {
                         error!("Failed to schedule MySQL task. Error: {}", error);
-                        return Err(-1)
+                        return Err(ExitStatus::Config)
                     }
                 };
-                services.push(mysql_service);
+                services.push(mysql_service.clone());
+                mysql_services.push(mysql_service);
             }
         }
     }
 
+    // Serve metrics and the API next to the scheduler.
+    if let Some(http_config) = &config.http {
+        if let Err(error) = http::serve(http_config, HttpState { services: services.clone(), catalog, api_token: http_config.api_token.clone() }).await {
+            error!("Failed to listen on {}. Error: {}", http_config.listen, error);
+            return Err(ExitStatus::Config)
+        }
+    }
+
+    // Alert about stale backups even when the scheduler never runs a job.
+    watchdog::spawn(mysql_services, catalog);
+
     // Start the scheduler.
     match sched.start().await {
         Ok(_) => (),
         Err(error) => {
             error!("Failed to start the scheduler due to error: {}", error);
-            return Err(-1)
+            return Err(ExitStatus::Failure)
         }
     };
 
@@ -134,7 +553,13 @@ async fn main() -> Result<(), i32> {
     #[cfg(unix)]
     {
         // Create a signal receiver for SIGTERM (Unix only)
-        let sigterm_future = signal(SignalKind::terminate())?;
+        let mut sigterm_future = match signal(SignalKind::terminate()) {
+            Ok(signal) => signal,
+            Err(error) => {
+                error!("Failed to register SIGTERM handler. Error: {}", error);
+                return Err(ExitStatus::Failure)
+            }
+        };
 
         // Wait for either Ctrl+C or SIGTERM signal
         tokio::select! {
@@ -159,8 +584,11 @@ async fn main() -> Result<(), i32> {
         Ok(_) => info!("Scheduler has been shutdown"),
         Err(error) => {
             error!("Failed to shutdown scheduler. Error: {}", error);
-            return Err(-1)
+            return Err(ExitStatus::Failure)
         }
     }
+    for service in &services {
+        service.shutdown().await;
+    }
     Ok(())
 }
\ No newline at end of file
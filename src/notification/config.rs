@@ -0,0 +1,98 @@
+use std::collections::HashMap;
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum NotificationEvent {
+    /// A run failed.
+    Failure,
+    /// A run succeeded.
+    Success,
+    /// A run succeeded after the previous one failed.
+    Recovered,
+    /// The retention policy removed backups.
+    RetentionDeleted,
+    /// Removing the backups the retention policy no longer keeps failed, the run went ahead regardless.
+    RetentionFailed,
+    /// The newest backup is older than the service's `max_age`.
+    Stale,
+    /// A run's size is far off the average of the ones before it.
+    SizeDeviation
+}
+
+impl NotificationEvent {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            NotificationEvent::Failure => "failure",
+            NotificationEvent::Success => "success",
+            NotificationEvent::Recovered => "recovered",
+            NotificationEvent::RetentionDeleted => "retention-deleted",
+            NotificationEvent::RetentionFailed => "retention-failed",
+            NotificationEvent::Stale => "stale",
+            NotificationEvent::SizeDeviation => "size-deviation"
+        }
+    }
+}
+
+/// Which notifications a target receives.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct NotificationFilter {
+    /// Defaults to every service.
+    pub services: Option<Vec<String>>,
+    /// Defaults to everything but `success` and `retention-deleted`, a target listening for `success` also hears about recoveries.
+    pub events: Option<Vec<NotificationEvent>>
+}
+
+impl NotificationFilter {
+    pub fn matches(&self, service: &str, event: NotificationEvent) -> bool {
+        let service_matches = self.services.as_ref().is_none_or(|services| services.iter().any(|name| name == service));
+        let events = self.events.clone().unwrap_or(vec![NotificationEvent::Failure, NotificationEvent::Recovered, NotificationEvent::RetentionFailed, NotificationEvent::Stale, NotificationEvent::SizeDeviation]);
+        let event_matches = events.contains(&event) || (event == NotificationEvent::Recovered && events.contains(&NotificationEvent::Success));
+        service_matches && event_matches
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct WebhookConfig {
+    pub url: String,
+    /// JSON body with `{{service}}`, `{{status}}`, `{{size}}`, `{{duration}}`, `{{error}}` etc. placeholders,
+    /// defaults to the whole notification as a JSON object.
+    pub body: Option<String>,
+    /// Extra request headers, e.g. `Authorization`.
+    pub headers: Option<HashMap<String, String>>,
+    #[serde(flatten)]
+    pub filter: NotificationFilter
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum SmtpTls {
+    /// Plain text, only for local relays.
+    None,
+    Starttls,
+    /// TLS from the start, usually on port 465.
+    Tls
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct EmailConfig {
+    pub host: String,
+    /// Defaults to 25 without TLS, 587 with STARTTLS and 465 with TLS.
+    pub port: Option<u16>,
+    /// Defaults to STARTTLS.
+    pub tls: Option<SmtpTls>,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    pub from: String,
+    pub to: Vec<String>,
+    #[serde(flatten)]
+    pub filter: NotificationFilter
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct NotificationsConfig {
+    #[serde(default)]
+    pub webhook: Vec<WebhookConfig>,
+    #[serde(default)]
+    pub email: Vec<EmailConfig>
+}
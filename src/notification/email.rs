@@ -0,0 +1,152 @@
+use std::fmt::Write;
+use std::time::Duration;
+use async_trait::async_trait;
+use lettre::{AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+use lettre::message::header::ContentType;
+use lettre::transport::smtp::authentication::Credentials;
+use crate::notification::{Notification, Notifier};
+use crate::notification::config::{EmailConfig, SmtpTls};
+use crate::utils::format_size;
+
+/// Mails notifications through an SMTP relay.
+pub struct EmailNotifier {
+    config: EmailConfig
+}
+
+impl EmailNotifier {
+    pub fn new(config: EmailConfig) -> EmailNotifier {
+        EmailNotifier { config }
+    }
+}
+
+fn format_body(notification: &Notification) -> String {
+    let mut body = String::new();
+    writeln!(body, "{}", notification.message()).unwrap();
+    writeln!(body).unwrap();
+    writeln!(body, "Service:  {}", notification.service).unwrap();
+    if let Some(job) = &notification.job {
+        writeln!(body, "Job:      {}", job).unwrap();
+    }
+    writeln!(body, "Host:     {}", notification.host).unwrap();
+    writeln!(body, "Status:   {}", notification.status).unwrap();
+    if let Some(run) = notification.run {
+        writeln!(body, "Run:      {}", run).unwrap();
+    }
+    if let Some(duration) = notification.duration {
+        writeln!(body, "Duration: {:.1}s", duration).unwrap();
+    }
+    if let Some(size) = notification.size {
+        writeln!(body, "Size:     {}", format_size(size.max(0) as u64)).unwrap();
+    }
+    for backup in &notification.backups {
+        writeln!(body, "Backup:   {}", backup).unwrap();
+    }
+    if let Some(error) = &notification.error {
+        writeln!(body, "\n{}", error).unwrap();
+    }
+    body
+}
+
+#[async_trait]
+impl Notifier for EmailNotifier {
+    async fn send(&self, notification: &Notification) -> Result<(), Box<dyn std::error::Error>> {
+        let mut builder = Message::builder()
+            .from(self.config.from.parse()?)
+            .subject(format!("{} on {}: {}", notification.label(), notification.host, notification.event.as_str()))
+            .header(ContentType::TEXT_PLAIN);
+        for to in &self.config.to {
+            builder = builder.to(to.parse()?);
+        }
+        let message = builder.body(format_body(notification))?;
+
+        let mut transport = match self.config.tls.unwrap_or(SmtpTls::Starttls) {
+            SmtpTls::None => AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(&self.config.host),
+            SmtpTls::Starttls => AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&self.config.host)?,
+            SmtpTls::Tls => AsyncSmtpTransport::<Tokio1Executor>::relay(&self.config.host)?
+        };
+        if let Some(port) = self.config.port {
+            transport = transport.port(port);
+        }
+        if let (Some(username), Some(password)) = (&self.config.username, &self.config.password) {
+            transport = transport.credentials(Credentials::new(username.clone(), password.clone()));
+        }
+        transport.timeout(Some(Duration::from_secs(30))).build().send(message).await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+    use tokio::net::TcpListener;
+    use crate::notification::config::{NotificationEvent, NotificationFilter};
+
+    /// Accepts a single message and returns what was sent as DATA.
+    async fn smtp_sink(listener: TcpListener) -> String {
+        let (stream, _) = listener.accept().await.unwrap();
+        let (reader, mut writer) = stream.into_split();
+        let mut lines = BufReader::new(reader).lines();
+        writer.write_all(b"220 sink ESMTP\r\n").await.unwrap();
+        let mut data = String::new();
+        let mut in_data = false;
+        while let Some(line) = lines.next_line().await.unwrap() {
+            if in_data {
+                if line == "." {
+                    in_data = false;
+                    writer.write_all(b"250 queued\r\n").await.unwrap();
+                } else {
+                    data.push_str(&line);
+                    data.push('\n');
+                }
+                continue;
+            }
+            let command = line.to_uppercase();
+            if command.starts_with("DATA") {
+                in_data = true;
+                writer.write_all(b"354 go ahead\r\n").await.unwrap();
+            } else if command.starts_with("QUIT") {
+                writer.write_all(b"221 bye\r\n").await.unwrap();
+                break;
+            } else {
+                writer.write_all(b"250 ok\r\n").await.unwrap();
+            }
+        }
+        data
+    }
+
+    #[tokio::test]
+    async fn test_send() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let sink = tokio::spawn(smtp_sink(listener));
+
+        let notifier = EmailNotifier::new(EmailConfig {
+            host: "127.0.0.1".to_string(),
+            port: Some(port),
+            tls: Some(SmtpTls::None),
+            username: None,
+            password: None,
+            from: "backups@example.com".to_string(),
+            to: vec!["dba@example.com".to_string()],
+            filter: NotificationFilter::default()
+        });
+        let notification = Notification {
+            event: NotificationEvent::RetentionDeleted,
+            service: "mysql-r1".to_string(),
+            job: None,
+            host: "db1".to_string(),
+            status: "deleted".to_string(),
+            run: None,
+            backups: vec![],
+            size: Some(2048),
+            duration: None,
+            error: None
+        };
+        notifier.send(&notification).await.unwrap();
+
+        let data = sink.await.unwrap();
+        assert!(data.contains("Subject: mysql-r1 on db1: retention-deleted"));
+        assert!(data.contains("Retention removed 0 backup(s) of mysql-r1 on db1, 2.0 KiB"));
+    }
+}
@@ -0,0 +1,222 @@
+pub mod config;
+pub mod email;
+pub mod webhook;
+
+use async_trait::async_trait;
+use log::{debug, error};
+use serde::Serialize;
+use uuid::Uuid;
+use crate::notification::config::{NotificationEvent, NotificationFilter, NotificationsConfig};
+use crate::notification::email::EmailNotifier;
+use crate::notification::webhook::WebhookNotifier;
+use crate::service::mysql::database::{MysqlBackupRow, RunRow};
+use crate::utils::format_size;
+
+/// Something that happened to a service, sent to every target which listens for it.
+#[derive(Debug, Serialize)]
+pub struct Notification {
+    pub event: NotificationEvent,
+    pub service: String,
+    /// The named backup job, when the service has several.
+    pub job: Option<String>,
+    pub host: String,
+    /// How the run ended, `succeeded` or `failed`, or `deleted` for backups the retention policy removed.
+    pub status: String,
+    pub run: Option<Uuid>,
+    /// The backups the run took, or the ones that were removed.
+    pub backups: Vec<Uuid>,
+    /// Bytes written by the run, or removed.
+    pub size: Option<i64>,
+    /// Seconds the run took.
+    pub duration: Option<f64>,
+    pub error: Option<String>
+}
+
+impl Notification {
+    /// How a finished run went, `previous` is the run before it and `backups` the ones it took.
+    pub fn for_run(run: &RunRow, previous: Option<&RunRow>, backups: &[MysqlBackupRow]) -> Notification {
+        let event = match run.status.as_str() {
+            "failed" => NotificationEvent::Failure,
+            _ if previous.is_some_and(|previous| previous.status == "failed") => NotificationEvent::Recovered,
+            _ => NotificationEvent::Success
+        };
+        Notification {
+            event,
+            service: run.service.clone(),
+            job: run.job.clone(),
+            host: run.host.clone().unwrap_or_default(),
+            status: run.status.clone(),
+            run: Some(run.uuid),
+            backups: backups.iter().map(|backup| backup.uuid).collect(),
+            size: if backups.is_empty() { None } else { Some(backups.iter().map(|backup| backup.size).sum()) },
+            duration: run.finished_at.map(|finished_at| (finished_at - run.started_at).num_milliseconds() as f64 / 1000.0),
+            error: run.error.clone()
+        }
+    }
+
+    /// A run which failed before it could be recorded, e.g. because the catalog is unavailable.
+    pub fn failure(service: &str, job: Option<&str>, host: &str, error: String) -> Notification {
+        Notification {
+            event: NotificationEvent::Failure,
+            service: service.to_string(),
+            job: job.map(str::to_string),
+            host: host.to_string(),
+            status: "failed".to_string(),
+            run: None,
+            backups: vec![],
+            size: None,
+            duration: None,
+            error: Some(error)
+        }
+    }
+
+    /// Backups the retention policy removed.
+    pub fn retention_deleted(service: &str, job: Option<&str>, host: &str, backups: &[MysqlBackupRow]) -> Notification {
+        Notification {
+            event: NotificationEvent::RetentionDeleted,
+            service: service.to_string(),
+            job: job.map(str::to_string),
+            host: host.to_string(),
+            status: "deleted".to_string(),
+            run: None,
+            backups: backups.iter().map(|backup| backup.uuid).collect(),
+            size: Some(backups.iter().map(|backup| backup.size).sum()),
+            duration: None,
+            error: None
+        }
+    }
+
+    /// Backups the retention policy no longer keeps which could not be removed.
+    pub fn retention_failed(service: &str, job: Option<&str>, host: &str, error: String) -> Notification {
+        Notification {
+            event: NotificationEvent::RetentionFailed,
+            service: service.to_string(),
+            job: job.map(str::to_string),
+            host: host.to_string(),
+            status: "failed".to_string(),
+            run: None,
+            backups: vec![],
+            size: None,
+            duration: None,
+            error: Some(error)
+        }
+    }
+
+    /// Something the watchdog noticed, `detail` explains what.
+    pub fn alert(event: NotificationEvent, service: &str, job: Option<&str>, host: &str, detail: String, backups: &[MysqlBackupRow]) -> Notification {
+        Notification {
+            event,
+            service: service.to_string(),
+            job: job.map(str::to_string),
+            host: host.to_string(),
+            status: event.as_str().to_string(),
+            run: backups.first().and_then(|backup| backup.run_uuid),
+            backups: backups.iter().map(|backup| backup.uuid).collect(),
+            size: if backups.is_empty() { None } else { Some(backups.iter().map(|backup| backup.size).sum()) },
+            duration: None,
+            error: Some(detail)
+        }
+    }
+
+    /// The service, followed by the job when there is one, e.g. `mysql-r1.hourly`.
+    pub fn label(&self) -> String {
+        match &self.job {
+            Some(job) => format!("{}.{}", self.service, job),
+            None => self.service.clone()
+        }
+    }
+
+    /// A one line summary for people.
+    pub fn message(&self) -> String {
+        let label = self.label();
+        let size = self.size.map(|size| format!(", {}", format_size(size.max(0) as u64))).unwrap_or_default();
+        match self.event {
+            NotificationEvent::Failure => format!("Backup of {} on {} failed: {}", label, self.host, self.error.as_deref().unwrap_or("unknown error")),
+            NotificationEvent::Success => format!("Backup of {} on {} succeeded in {:.0}s{}", label, self.host, self.duration.unwrap_or_default(), size),
+            NotificationEvent::Recovered => format!("Backup of {} on {} succeeded again in {:.0}s{}", label, self.host, self.duration.unwrap_or_default(), size),
+            NotificationEvent::RetentionDeleted => format!("Retention removed {} backup(s) of {} on {}{}", self.backups.len(), label, self.host, size),
+            NotificationEvent::RetentionFailed => format!("Retention of {} on {} failed: {}", label, self.host, self.error.as_deref().unwrap_or("unknown error")),
+            NotificationEvent::Stale | NotificationEvent::SizeDeviation => format!("Backups of {} on {} need attention: {}", label, self.host, self.error.as_deref().unwrap_or_default())
+        }
+    }
+}
+
+/// A place notifications are delivered to.
+#[async_trait]
+pub trait Notifier: Send + Sync {
+    async fn send(&self, notification: &Notification) -> Result<(), Box<dyn std::error::Error>>;
+}
+
+fn create_notifiers(config: &NotificationsConfig) -> Vec<(&NotificationFilter, Box<dyn Notifier>)> {
+    let mut notifiers: Vec<(&NotificationFilter, Box<dyn Notifier>)> = vec![];
+    for webhook_config in &config.webhook {
+        notifiers.push((&webhook_config.filter, Box::new(WebhookNotifier::new(webhook_config.clone()))));
+    }
+    for email_config in &config.email {
+        notifiers.push((&email_config.filter, Box::new(EmailNotifier::new(email_config.clone()))));
+    }
+    notifiers
+}
+
+/// Sends the notification to every target listening for it, a target which fails is logged and skipped.
+pub async fn notify(config: Option<&NotificationsConfig>, notification: &Notification) {
+    let Some(config) = config else { return };
+    for (filter, notifier) in create_notifiers(config) {
+        if !filter.matches(&notification.service, notification.event) {
+            continue;
+        }
+        match notifier.send(notification).await.map_err(|error| error.to_string()) {
+            Ok(_) => debug!("Sent {} notification for {}", notification.event.as_str(), notification.service),
+            Err(error) => error!("Failed to send {} notification for {}, error: {}", notification.event.as_str(), notification.service, error)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sqlx::types::chrono::NaiveDate;
+
+    fn run(status: &str) -> RunRow {
+        let started_at = NaiveDate::from_ymd_opt(2024, 4, 16).unwrap().and_hms_opt(18, 0, 0).unwrap();
+        RunRow {
+            uuid: Uuid::from_u128(1),
+            service: "mysql-r1".to_string(),
+            trigger: "cron".to_string(),
+            started_at,
+            finished_at: Some(started_at + std::time::Duration::from_secs(90)),
+            status: status.to_string(),
+            error: None,
+            stderr: None,
+            host: Some("db1".to_string()),
+            job: None
+        }
+    }
+
+    #[test]
+    fn test_run_events() {
+        assert_eq!(Notification::for_run(&run("failed"), None, &[]).event, NotificationEvent::Failure);
+        assert_eq!(Notification::for_run(&run("succeeded"), Some(&run("succeeded")), &[]).event, NotificationEvent::Success);
+        let recovered = Notification::for_run(&run("succeeded"), Some(&run("failed")), &[]);
+        assert_eq!(recovered.event, NotificationEvent::Recovered);
+        assert_eq!(recovered.duration, Some(90.0));
+        assert_eq!(recovered.message(), "Backup of mysql-r1 on db1 succeeded again in 90s");
+    }
+
+    #[test]
+    fn test_filter() {
+        let filter = NotificationFilter::default();
+        assert!(filter.matches("mysql-r1", NotificationEvent::Failure));
+        assert!(!filter.matches("mysql-r1", NotificationEvent::Success));
+        assert!(filter.matches("mysql-r1", NotificationEvent::RetentionFailed));
+
+        let filter: NotificationFilter = toml::from_str(r#"
+services = ["mysql-r1"]
+events = ["success", "retention-deleted"]
+        "#).unwrap();
+        assert!(filter.matches("mysql-r1", NotificationEvent::Recovered));
+        assert!(filter.matches("mysql-r1", NotificationEvent::RetentionDeleted));
+        assert!(!filter.matches("mysql-r1", NotificationEvent::Failure));
+        assert!(!filter.matches("mysql-r2", NotificationEvent::Success));
+    }
+}
@@ -0,0 +1,146 @@
+use std::time::Duration;
+use async_trait::async_trait;
+use reqwest::header::CONTENT_TYPE;
+use crate::notification::{Notification, Notifier};
+use crate::notification::config::WebhookConfig;
+use crate::utils::format_size;
+
+/// POSTs notifications as JSON.
+pub struct WebhookNotifier {
+    config: WebhookConfig
+}
+
+impl WebhookNotifier {
+    pub fn new(config: WebhookConfig) -> WebhookNotifier {
+        WebhookNotifier { config }
+    }
+}
+
+/// Fills the `{{name}}` placeholders of a body template, values are escaped so they can sit inside JSON strings.
+pub fn render_template(template: &str, notification: &Notification) -> String {
+    // Numbers sit outside of quotes, so a missing one has to become `null` to keep the JSON valid.
+    let number = |value: Option<String>| value.unwrap_or_else(|| "null".to_string());
+    let values = [
+        ("event", notification.event.as_str().to_string()),
+        ("service", notification.service.clone()),
+        ("job", notification.job.clone().unwrap_or_default()),
+        ("host", notification.host.clone()),
+        ("status", notification.status.clone()),
+        ("run", notification.run.map(|run| run.to_string()).unwrap_or_default()),
+        ("backups", notification.backups.iter().map(|uuid| uuid.to_string()).collect::<Vec<_>>().join(",")),
+        ("size", number(notification.size.map(|size| size.to_string()))),
+        ("size_human", notification.size.map(|size| format_size(size.max(0) as u64)).unwrap_or_default()),
+        ("duration", number(notification.duration.map(|duration| duration.to_string()))),
+        ("error", notification.error.clone().unwrap_or_default()),
+        ("message", notification.message()),
+    ];
+
+    // A single pass, so placeholders inside the values themselves are left alone.
+    let mut body = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find("{{") {
+        body.push_str(&rest[..start]);
+        let placeholder = &rest[start..];
+        let value = placeholder.find("}}").and_then(|end| {
+            let name = &placeholder[2..end];
+            values.iter().find(|(key, _)| *key == name).map(|(_, value)| (value, end + 2))
+        });
+        match value {
+            Some((value, length)) => {
+                let escaped = serde_json::to_string(value).unwrap();
+                body.push_str(&escaped[1..escaped.len() - 1]);
+                rest = &placeholder[length..];
+            }
+            None => {
+                body.push_str("{{");
+                rest = &placeholder[2..];
+            }
+        }
+    }
+    body.push_str(rest);
+    body
+}
+
+#[async_trait]
+impl Notifier for WebhookNotifier {
+    async fn send(&self, notification: &Notification) -> Result<(), Box<dyn std::error::Error>> {
+        let body = match &self.config.body {
+            Some(template) => render_template(template, notification),
+            None => serde_json::to_string(notification)?
+        };
+
+        let client = reqwest::Client::builder().timeout(Duration::from_secs(30)).build()?;
+        let mut request = client.post(&self.config.url).header(CONTENT_TYPE, "application/json").body(body);
+        for (name, value) in self.config.headers.iter().flatten() {
+            request = request.header(name, value);
+        }
+        request.send().await?.error_for_status()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::Router;
+    use axum::routing::post;
+    use tokio::net::TcpListener;
+    use tokio::sync::mpsc;
+    use uuid::Uuid;
+    use crate::notification::config::{NotificationEvent, NotificationFilter};
+
+    fn notification() -> Notification {
+        Notification {
+            event: NotificationEvent::Failure,
+            service: "mysql-r1".to_string(),
+            job: None,
+            host: "db1".to_string(),
+            status: "failed".to_string(),
+            run: Some(Uuid::from_u128(1)),
+            backups: vec![],
+            size: None,
+            duration: Some(1.5),
+            error: Some("mysqldump exited with \"1\"".to_string())
+        }
+    }
+
+    #[test]
+    fn test_render_template() {
+        let body = render_template(r#"{"text": "{{service}} {{status}}: {{error}}", "duration": {{duration}}}"#, &notification());
+        let body: serde_json::Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(body["text"], "mysql-r1 failed: mysqldump exited with \"1\"");
+        assert_eq!(body["duration"], 1.5);
+
+        // A missing number is null, and placeholders coming in with the values are not expanded again.
+        let mut notification = notification();
+        notification.duration = None;
+        notification.error = Some("{{message}}".to_string());
+        let body = render_template(r#"{"error": "{{error}}", "size": {{size}}, "duration": {{duration}}, "other": "{{unknown}}"}"#, &notification);
+        let body: serde_json::Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(body["error"], "{{message}}");
+        assert!(body["size"].is_null());
+        assert!(body["duration"].is_null());
+        assert_eq!(body["other"], "{{unknown}}");
+    }
+
+    #[tokio::test]
+    async fn test_send() {
+        let (sender, mut receiver) = mpsc::unbounded_channel();
+        let app = Router::new().route("/hook", post(move |body: String| async move { sender.send(body).unwrap(); }));
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+        let notifier = WebhookNotifier::new(WebhookConfig {
+            url: format!("http://{}/hook", address),
+            body: None,
+            headers: None,
+            filter: NotificationFilter::default()
+        });
+        notifier.send(&notification()).await.unwrap();
+        let body: serde_json::Value = serde_json::from_str(&receiver.recv().await.unwrap()).unwrap();
+        assert_eq!(body["event"], "failure");
+        assert_eq!(body["service"], "mysql-r1");
+        assert_eq!(body["run"], "00000000-0000-0000-0000-000000000001");
+    }
+}
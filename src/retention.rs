@@ -0,0 +1,182 @@
+use std::collections::{HashMap, HashSet};
+use std::time::Duration;
+use sqlx::types::chrono::NaiveDateTime;
+use uuid::Uuid;
+use crate::config::RetentionConfig;
+
+impl RetentionConfig {
+    /// Layers `other` on top of this policy, every rule set in `other` wins.
+    pub fn merge(&self, other: &RetentionConfig) -> RetentionConfig {
+        RetentionConfig {
+            keep_within_days: other.keep_within_days.or(self.keep_within_days),
+            keep_hourly: other.keep_hourly.or(self.keep_hourly),
+            keep_daily: other.keep_daily.or(self.keep_daily),
+            keep_weekly: other.keep_weekly.or(self.keep_weekly),
+            keep_monthly: other.keep_monthly.or(self.keep_monthly),
+            keep_yearly: other.keep_yearly.or(self.keep_yearly),
+            keep_min_count: other.keep_min_count.or(self.keep_min_count),
+        }
+    }
+
+    /// Whether the policy would ever delete anything, `keep_min_count` is only a floor under the other rules.
+    pub fn is_empty(&self) -> bool {
+        self.keep_within_days.is_none()
+            && self.keep_hourly.is_none()
+            && self.keep_daily.is_none()
+            && self.keep_weekly.is_none()
+            && self.keep_monthly.is_none()
+            && self.keep_yearly.is_none()
+    }
+
+    /// Returns the indices of the backups, given by their creation time, that are not kept by any rule.
+    pub fn expired(&self, created_at: &[NaiveDateTime], now: NaiveDateTime) -> Vec<usize> {
+        if self.is_empty() {
+            return vec![];
+        }
+
+        // Newest first, every rule keeps the newest backup of each period.
+        let mut order: Vec<usize> = (0..created_at.len()).collect();
+        order.sort_by(|a, b| created_at[*b].cmp(&created_at[*a]));
+
+        let mut kept: HashSet<usize> = HashSet::new();
+        if let Some(count) = self.keep_min_count {
+            kept.extend(order.iter().take(count as usize));
+        }
+        if let Some(days) = self.keep_within_days {
+            let cutoff = now - Duration::from_secs(days * 24 * 60 * 60);
+            kept.extend(order.iter().filter(|index| created_at[**index] >= cutoff));
+        }
+
+        let buckets = [
+            (self.keep_hourly, "%Y-%m-%d %H"),
+            (self.keep_daily, "%Y-%m-%d"),
+            (self.keep_weekly, "%G-%V"),
+            (self.keep_monthly, "%Y-%m"),
+            (self.keep_yearly, "%Y"),
+        ];
+        for (count, format) in buckets {
+            let Some(count) = count else { continue };
+            let mut periods: HashSet<String> = HashSet::new();
+            for index in &order {
+                if periods.len() >= count as usize {
+                    break;
+                }
+                if periods.insert(created_at[*index].format(format).to_string()) {
+                    kept.insert(*index);
+                }
+            }
+        }
+
+        let mut expired: Vec<usize> = (0..created_at.len()).filter(|index| !kept.contains(index)).collect();
+        expired.sort();
+        expired
+    }
+}
+
+/// Narrows `expired` down to whole chains, given each backup's uuid and the uuid it was taken on top of.
+/// A chain only expires once every member has, since any member depends on every one before it.
+pub fn expired_chains(links: &[(Uuid, Option<Uuid>)], expired: &[usize]) -> Vec<usize> {
+    let parents: HashMap<Uuid, Option<Uuid>> = links.iter().cloned().collect();
+
+    // Follow base uuids to the full backup, a base which is no longer known ends the chain.
+    let root = |uuid: Uuid| {
+        let mut current = uuid;
+        let mut seen = HashSet::new();
+        while let Some(Some(parent)) = parents.get(&current) {
+            if !parents.contains_key(parent) || !seen.insert(current) {
+                break;
+            }
+            current = *parent;
+        }
+        current
+    };
+
+    let roots: Vec<Uuid> = links.iter().map(|(uuid, _)| root(*uuid)).collect();
+    let expired: HashSet<usize> = expired.iter().cloned().collect();
+    let alive_roots: HashSet<Uuid> = (0..links.len()).filter(|index| !expired.contains(index)).map(|index| roots[index]).collect();
+
+    let mut result: Vec<usize> = expired.into_iter().filter(|index| !alive_roots.contains(&roots[*index])).collect();
+    result.sort();
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sqlx::types::chrono::NaiveDate;
+
+    fn at(day: u32, hour: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2024, 4, day).unwrap().and_hms_opt(hour, 0, 0).unwrap()
+    }
+
+    #[test]
+    fn test_empty_policy_keeps_everything() {
+        let policy = RetentionConfig::default();
+        assert!(policy.expired(&[at(1, 0), at(2, 0)], at(30, 0)).is_empty());
+    }
+
+    #[test]
+    fn test_min_count_alone_keeps_everything() {
+        let policy = RetentionConfig { keep_min_count: Some(1), ..Default::default() };
+        assert!(policy.is_empty());
+        assert!(policy.expired(&[at(1, 0), at(2, 0), at(3, 0)], at(30, 0)).is_empty());
+    }
+
+    #[test]
+    fn test_daily_keeps_newest_per_day() {
+        let policy = RetentionConfig { keep_daily: Some(2), ..Default::default() };
+        let backups = [at(1, 1), at(1, 12), at(2, 1), at(2, 12), at(3, 1), at(3, 12)];
+        // Only the last backup of the two newest days survives.
+        assert_eq!(policy.expired(&backups, at(3, 13)), vec![0, 1, 2, 4]);
+    }
+
+    #[test]
+    fn test_rules_are_combined() {
+        let policy = RetentionConfig {
+            keep_daily: Some(1),
+            keep_monthly: Some(2),
+            keep_min_count: Some(2),
+            ..Default::default()
+        };
+        let backups = [
+            NaiveDate::from_ymd_opt(2024, 2, 10).unwrap().and_hms_opt(0, 0, 0).unwrap(),
+            NaiveDate::from_ymd_opt(2024, 3, 10).unwrap().and_hms_opt(0, 0, 0).unwrap(),
+            at(1, 0),
+            at(2, 0),
+            at(3, 0),
+        ];
+        // The newest two by count, the newest of April by day and month, the newest of March by month.
+        assert_eq!(policy.expired(&backups, at(3, 1)), vec![0, 2]);
+    }
+
+    #[test]
+    fn test_keep_within_days() {
+        let policy = RetentionConfig { keep_within_days: Some(2), ..Default::default() };
+        assert_eq!(policy.expired(&[at(1, 0), at(2, 0), at(3, 0)], at(3, 12)), vec![0]);
+    }
+
+    #[test]
+    fn test_expired_chains() {
+        let full = Uuid::from_u128(1);
+        let incremental = Uuid::from_u128(2);
+        let second_incremental = Uuid::from_u128(3);
+        let other_full = Uuid::from_u128(4);
+        let links = [(full, None), (incremental, Some(full)), (second_incremental, Some(incremental)), (other_full, None)];
+
+        // The newest incremental is still kept, so its whole chain stays.
+        assert_eq!(expired_chains(&links, &[0, 1, 3]), vec![3]);
+        // Once every member expired the chain goes as a unit.
+        assert_eq!(expired_chains(&links, &[0, 1, 2]), vec![0, 1, 2]);
+        // Incrementals whose base is already gone form their own chain.
+        assert_eq!(expired_chains(&links[1..], &[0, 1]), vec![0, 1]);
+    }
+
+    #[test]
+    fn test_merge_prefers_override() {
+        let global = RetentionConfig { keep_daily: Some(7), keep_weekly: Some(4), ..Default::default() };
+        let service = RetentionConfig { keep_daily: Some(14), ..Default::default() };
+        let merged = global.merge(&service);
+        assert_eq!(merged.keep_daily, Some(14));
+        assert_eq!(merged.keep_weekly, Some(4));
+    }
+}
@@ -1,2 +1,3 @@
+#[allow(clippy::module_inception)]
 pub mod service;
 pub mod mysql;
\ No newline at end of file
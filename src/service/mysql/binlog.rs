@@ -0,0 +1,579 @@
+use std::path::{Path, PathBuf};
+use std::process::{ExitStatus, Stdio};
+use std::str::FromStr;
+use async_trait::async_trait;
+use log::{debug, info, warn};
+use sqlx::mysql::MySqlConnectOptions;
+use sqlx::{MySqlPool, Row};
+use sqlx::types::chrono::{Local, NaiveDate, NaiveDateTime, NaiveTime, TimeZone, Utc};
+use tokio::fs;
+use tokio::io::{AsyncBufReadExt, BufReader};
+use tokio::process::Command;
+use uuid::{NoContext, Timestamp, Uuid};
+use which::which;
+use crate::CATALOG;
+use crate::catalog::Catalog;
+use crate::service::mysql::config::BinlogConfig;
+use crate::service::mysql::database::{BinlogRow, MysqlBackupRow};
+use crate::service::mysql::gtid::{parse_gtid, GtidSet};
+use crate::service::mysql::mysql_defaults::MySqlDefaultsReader;
+use crate::service::mysql::mysql_service::MySQLService;
+use crate::service::mysql::restore::RestoreOptions;
+use crate::utils::get_size;
+
+/// What we learn about an archived binary log from the `mysqlbinlog` text output.
+#[derive(Debug, Default)]
+pub struct BinlogSummary {
+    pub end_position: i64,
+    /// Event timestamps, in the local time zone `mysqlbinlog` prints them in.
+    pub first_event_at: Option<NaiveDateTime>,
+    pub last_event_at: Option<NaiveDateTime>,
+    pub previous_gtids: GtidSet,
+    pub gtids: GtidSet,
+    in_previous_gtids: bool,
+}
+
+impl BinlogSummary {
+    /// Feeds a single line of `mysqlbinlog` output into the summary.
+    pub fn parse_line(&mut self, line: &str) {
+        // The Previous-GTIDs event is followed by one or more comment lines holding the set.
+        if self.in_previous_gtids {
+            match line.strip_prefix("# ") {
+                Some(set) if !set.starts_with("at ") => {
+                    if let Ok(set) = GtidSet::from_str(set.trim().trim_end_matches(',')) {
+                        self.previous_gtids.union(&set);
+                    }
+                    return;
+                }
+                _ => self.in_previous_gtids = false
+            }
+        }
+
+        if let Some(gtid) = line.strip_prefix("SET @@SESSION.GTID_NEXT= '") {
+            if let Some((gtid, _)) = gtid.split_once('\'') {
+                if let Ok((source, transaction)) = parse_gtid(gtid) {
+                    self.gtids.add(&source, transaction);
+                }
+            }
+            return;
+        }
+
+        // Event headers look like `#240416 18:08:21 server id 1  end_log_pos 126 CRC32 0x...`.
+        if let Some(header) = line.strip_prefix('#') {
+            let mut tokens = header.split_whitespace();
+            let timestamp = match (tokens.next(), tokens.next()) {
+                (Some(date), Some(time)) => parse_event_timestamp(date, time),
+                _ => None
+            };
+            let Some(timestamp) = timestamp else { return };
+
+            let mut tokens = header.split_whitespace().skip_while(|token| *token != "end_log_pos");
+            if let Some(position) = tokens.nth(1).and_then(|position| position.parse::<i64>().ok()) {
+                self.end_position = self.end_position.max(position);
+            }
+            if self.first_event_at.is_none() {
+                self.first_event_at = Some(timestamp);
+            }
+            self.last_event_at = Some(timestamp);
+            if header.trim_end().ends_with("Previous-GTIDs") {
+                self.in_previous_gtids = true;
+            }
+        }
+    }
+}
+
+/// Finds where the transaction after a GTID starts in `mysqlbinlog` output, replaying up to there applies the GTID
+/// and nothing committed after it, whichever server that came from.
+#[derive(Debug)]
+pub struct GtidStop {
+    source: String,
+    transaction: u64,
+    position: Option<i64>,
+    /// Whether the transaction is in the binary log at all.
+    pub found: bool,
+    /// Where the next transaction starts, `None` when the transaction is the last one in the file.
+    pub stop_position: Option<i64>,
+}
+
+impl GtidStop {
+    pub fn new(source: &str, transaction: u64) -> GtidStop {
+        GtidStop { source: source.to_lowercase(), transaction, position: None, found: false, stop_position: None }
+    }
+
+    pub fn parse_line(&mut self, line: &str) {
+        if self.stop_position.is_some() {
+            return;
+        }
+        if let Some(position) = line.strip_prefix("# at ") {
+            self.position = position.trim().parse().ok();
+            return;
+        }
+        if let Some(gtid) = line.strip_prefix("SET @@SESSION.GTID_NEXT= '") {
+            if let Some(Ok((source, transaction))) = gtid.split_once('\'').map(|(gtid, _)| parse_gtid(gtid)) {
+                self.found |= source.to_lowercase() == self.source && transaction == self.transaction;
+            }
+            return;
+        }
+        // Every transaction starts with a GTID event, the one after ours ends it.
+        if self.found && line.starts_with('#') && line.split_whitespace().any(|token| token == "GTID" || token == "Anonymous_GTID") {
+            self.stop_position = self.position;
+        }
+    }
+}
+
+/// Where in the binary log stream a backup was taken.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct BinlogCoordinates {
+    pub file: String,
+    pub position: i64,
+    pub gtid_executed: Option<String>,
+}
+
+/// Parses the `CHANGE MASTER TO`/`CHANGE REPLICATION SOURCE TO` and `GTID_PURGED` statements mysqldump writes
+/// at the top of a dump taken with `--source-data=2`/`--master-data=2`.
+pub fn parse_dump_header(header: &str) -> Option<BinlogCoordinates> {
+    let file_start = header.find("_LOG_FILE='")? + "_LOG_FILE='".len();
+    let file = header[file_start..].split('\'').next()?.to_string();
+    let position_start = header.find("_LOG_POS=")? + "_LOG_POS=".len();
+    let position = header[position_start..].chars().take_while(|c| c.is_ascii_digit()).collect::<String>().parse().ok()?;
+
+    // The purged set may span several lines, and is optionally preceded by a quoted '+'.
+    let gtid_executed = header.find("GTID_PURGED=").and_then(|start| {
+        let statement = header[start..].split(';').next()?;
+        let set = statement.split('\'').enumerate()
+            .filter(|(index, _)| index % 2 == 1)
+            .map(|(_, value)| value)
+            .last()?;
+        let set = set.split_whitespace().collect::<String>();
+        (!set.is_empty()).then_some(set)
+    });
+
+    Some(BinlogCoordinates { file, position, gtid_executed })
+}
+
+/// Parses `xtrabackup_binlog_info`, which holds the file, position and executed GTID set separated by tabs.
+pub fn parse_xtrabackup_binlog_info(contents: &str) -> Option<BinlogCoordinates> {
+    let mut fields = contents.splitn(3, '\t');
+    let file = fields.next()?.trim().to_string();
+    let position = fields.next()?.trim().parse().ok()?;
+    let gtid_executed = fields.next()
+        .map(|set| set.split_whitespace().collect::<String>())
+        .filter(|set| !set.is_empty());
+    if file.is_empty() {
+        return None;
+    }
+    Some(BinlogCoordinates { file, position, gtid_executed })
+}
+
+/// Reads the `binlog_pos` line of `xtrabackup_info`, which is all a streamed backup leaves in its `--extra-lsndir`, e.g.
+/// `binlog_pos = filename 'binlog.000003', position '157', GTID of the last change 'aaaa:1-10'`.
+pub fn parse_xtrabackup_info(contents: &str) -> Option<BinlogCoordinates> {
+    let line = contents.lines().find_map(|line| line.strip_prefix("binlog_pos = "))?;
+    let quoted = |key: &str| {
+        let rest = &line[line.find(key)? + key.len()..];
+        let rest = rest.strip_prefix('\'')?;
+        Some(rest[..rest.find('\'')?].to_string())
+    };
+    let file = quoted("filename ")?;
+    let position = quoted("position ")?.parse().ok()?;
+    let gtid_executed = quoted("GTID of the last change ")
+        .map(|set| set.split_whitespace().collect::<String>())
+        .filter(|set| !set.is_empty());
+    Some(BinlogCoordinates { file, position, gtid_executed })
+}
+
+fn parse_event_timestamp(date: &str, time: &str) -> Option<NaiveDateTime> {
+    if date.len() != 6 {
+        return None;
+    }
+    let date = NaiveDate::parse_from_str(&format!("20{}", date), "%Y%m%d").ok()?;
+    let mut parts = time.split(':').map(|part| part.parse::<u32>());
+    let time = match (parts.next(), parts.next(), parts.next()) {
+        (Some(Ok(hour)), Some(Ok(minute)), Some(Ok(second))) => NaiveTime::from_hms_opt(hour, minute, second)?,
+        _ => return None
+    };
+    Some(date.and_time(time))
+}
+
+fn local_to_utc(timestamp: NaiveDateTime) -> NaiveDateTime {
+    match Local.from_local_datetime(&timestamp).earliest() {
+        Some(local) => local.naive_utc(),
+        None => timestamp
+    }
+}
+
+fn utc_to_local(timestamp: NaiveDateTime) -> String {
+    Utc.from_utc_datetime(&timestamp).with_timezone(&Local).format("%Y-%m-%d %H:%M:%S").to_string()
+}
+
+/// Lists the archived binary logs in order, the last one is the one `mysqlbinlog` is still writing to.
+async fn list_archived_files(directory: &PathBuf) -> Result<Vec<PathBuf>, Box<dyn std::error::Error>> {
+    let mut files = vec![];
+    if !directory.is_dir() {
+        return Ok(files);
+    }
+
+    let mut entries = fs::read_dir(directory).await?;
+    while let Some(entry) = entries.next_entry().await? {
+        let path = entry.path();
+        let hidden = entry.file_name().to_str().is_none_or(|name| name.starts_with('.'));
+        if path.is_file() && !hidden {
+            files.push(path);
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// Feeds the text `mysqlbinlog` prints for an archived binary log to `parse_line`, line by line.
+async fn read_binlog(command_path: &Path, path: &Path, mut parse_line: impl FnMut(&str) + Send) -> Result<ExitStatus, std::io::Error> {
+    let mut child = Command::new(command_path)
+        .arg("--no-defaults")
+        .arg(path)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .kill_on_drop(true)
+        .spawn()?;
+
+    let mut lines = BufReader::new(child.stdout.take().unwrap()).lines();
+    while let Some(line) = lines.next_line().await? {
+        parse_line(&line);
+    }
+    child.wait().await
+}
+
+#[async_trait]
+pub trait BinlogArchiver {
+    fn binlog_directory(&self, binlog_config: &BinlogConfig) -> PathBuf;
+
+    async fn archive_binlogs(&self, binlog_config: &BinlogConfig) -> Result<(), Box<dyn std::error::Error>>;
+
+    async fn index_binlogs(&self, binlog_config: &BinlogConfig) -> Result<(), Box<dyn std::error::Error>>;
+
+    async fn replay_binlogs(&self, catalog: &dyn Catalog, backup: &MysqlBackupRow, options: &RestoreOptions) -> Result<(), Box<dyn std::error::Error>>;
+}
+
+#[async_trait]
+impl BinlogArchiver for MySQLService {
+    fn binlog_directory(&self, binlog_config: &BinlogConfig) -> PathBuf {
+        match &binlog_config.basedir {
+            Some(basedir) => PathBuf::from(basedir),
+            None => PathBuf::from(&self.backup_config.basedir).join("binlogs").join(&self.name)
+        }
+    }
+
+    async fn archive_binlogs(&self, binlog_config: &BinlogConfig) -> Result<(), Box<dyn std::error::Error>> {
+        let defaults = self.get_defaults_file().await?;
+        let defaults_path = defaults.path();
+        let directory = self.binlog_directory(binlog_config);
+        fs::create_dir_all(&directory).await?;
+
+        // Resume from the newest archived file, it is fetched again in full since it may be incomplete.
+        let archived = list_archived_files(&directory).await?;
+        let start_file = if let Some(last) = archived.last() {
+            last.file_name().unwrap().to_str().unwrap().to_string()
+        } else if let Some(start_file) = &binlog_config.start_file {
+            start_file.clone()
+        } else {
+            let connection_config = MySqlConnectOptions::from_defaults_file(defaults_path)?;
+            let pool = MySqlPool::connect_lazy_with(connection_config);
+            let row = sqlx::query("SHOW BINARY LOGS").fetch_one(&pool).await?;
+            pool.close().await;
+            row.get::<String, _>(0)
+        };
+        info!("Archiving binary logs of {} starting at {} into {}", self.name, start_file, directory.to_str().unwrap());
+
+        let command_path = which("mysqlbinlog")?;
+        let mut cmd = Command::new(command_path);
+        cmd.arg(format!("--defaults-file={}", defaults_path.to_str().unwrap()));
+        cmd.arg("--read-from-remote-server");
+        cmd.arg("--raw");
+        cmd.arg("--stop-never");
+        cmd.arg(format!("--result-file={}/", directory.to_str().unwrap()));
+        cmd.arg(start_file);
+
+        // The archiver only returns once mysqlbinlog loses its connection.
+        let status = cmd.stdout(Stdio::null()).kill_on_drop(true).status().await?;
+        if status.success() {
+            Ok(())
+        } else {
+            Err(format!("mysqlbinlog exited with {}.", status).into())
+        }
+    }
+
+    async fn index_binlogs(&self, binlog_config: &BinlogConfig) -> Result<(), Box<dyn std::error::Error>> {
+        let catalog = CATALOG.get().unwrap();
+        let mut archived = list_archived_files(&self.binlog_directory(binlog_config)).await?;
+        let indexed = catalog.find_binlogs(&self.name).await?;
+
+        // The newest file is still being written to.
+        archived.pop();
+
+        let command_path = which("mysqlbinlog")?;
+        for path in archived {
+            let file_name = path.file_name().unwrap().to_str().unwrap().to_string();
+            if indexed.iter().any(|binlog| binlog.file_name == file_name) {
+                continue;
+            }
+
+            debug!("Indexing binary log {}", path.to_str().unwrap());
+            let mut summary = BinlogSummary::default();
+            let status = read_binlog(&command_path, &path, |line| summary.parse_line(line)).await?;
+            if !status.success() {
+                warn!("Failed to read binary log {}, mysqlbinlog exited with {}.", path.to_str().unwrap(), status);
+                continue;
+            }
+
+            catalog.insert_binlog(&BinlogRow {
+                uuid: Uuid::new_v7(Timestamp::now(NoContext)),
+                service: self.name.clone(),
+                file_name: file_name.clone(),
+                path: path.to_str().unwrap().to_string(),
+                start_position: 4,
+                end_position: summary.end_position,
+                first_event_at: summary.first_event_at.map(local_to_utc),
+                last_event_at: summary.last_event_at.map(local_to_utc),
+                previous_gtids: (!summary.previous_gtids.is_empty()).then(|| summary.previous_gtids.to_string()),
+                gtids: (!summary.gtids.is_empty()).then(|| summary.gtids.to_string()),
+                size: get_size(&path)? as i64,
+                created_at: Utc::now().naive_utc(),
+                host: Some(catalog.host().to_string())
+            }).await?;
+            info!("Archived binary log {} ({} -> {}) recorded.", file_name, summary.previous_gtids, summary.gtids);
+        }
+        Ok(())
+    }
+
+    async fn replay_binlogs(&self, catalog: &dyn Catalog, backup: &MysqlBackupRow, options: &RestoreOptions) -> Result<(), Box<dyn std::error::Error>> {
+        let binlog_config = match &self.config.binlog {
+            Some(binlog_config) => binlog_config,
+            None => return Err(format!("Service {} does not archive binary logs.", self.name).into())
+        };
+
+        // Everything before the coordinates the backup was taken at is already contained in it. Older backups
+        // without coordinates fall back to the time the backup was recorded.
+        let indexed = catalog.find_binlogs(&self.name).await?;
+        let mut files: Vec<PathBuf> = indexed.iter()
+            .filter(|binlog| match &backup.binlog_file {
+                Some(binlog_file) => binlog.file_name >= *binlog_file,
+                None => binlog.last_event_at.is_none_or(|last_event_at| last_event_at >= backup.created_at)
+            })
+            .map(|binlog| PathBuf::from(&binlog.path))
+            .collect();
+
+        // Files that are not indexed yet, including the one still being written, come after the indexed ones.
+        let last_indexed = indexed.last().map(|binlog| binlog.file_name.clone()).unwrap_or_default();
+        for path in list_archived_files(&self.binlog_directory(binlog_config)).await? {
+            let file_name = path.file_name().unwrap().to_str().unwrap();
+            let after_backup = backup.binlog_file.as_ref().is_none_or(|binlog_file| file_name >= binlog_file.as_str());
+            if file_name > last_indexed.as_str() && after_backup {
+                files.push(path);
+            }
+        }
+
+        let command_path = which("mysqlbinlog")?;
+        let mut cmd = Command::new(&command_path);
+        cmd.arg("--no-defaults");
+        if let (Some(binlog_file), Some(position)) = (&backup.binlog_file, backup.binlog_position) {
+            info!("Backup {} was taken at {}:{}, executed GTIDs: {}", backup.uuid, binlog_file, position, backup.gtid_executed.as_deref().unwrap_or("none"));
+        }
+        match (&backup.binlog_file, backup.binlog_position) {
+            (Some(binlog_file), Some(position)) if files.first().is_some_and(|path| path.ends_with(binlog_file)) => {
+                cmd.arg(format!("--start-position={}", position));
+            }
+            _ => {
+                cmd.arg(format!("--start-datetime={}", utc_to_local(backup.created_at)));
+            }
+        }
+        if let Some(until) = options.until {
+            cmd.arg(format!("--stop-datetime={}", utc_to_local(until)));
+        }
+
+        // Stop right after the given transaction, nothing committed after it is applied, whichever server it came from.
+        if let Some(until_gtid) = &options.until_gtid {
+            let (source, transaction) = parse_gtid(until_gtid)?;
+            if backup.gtid_executed.as_ref().and_then(|gtids| GtidSet::from_str(gtids).ok()).is_some_and(|gtids| gtids.contains(&source, transaction)) {
+                info!("Backup {} already contains {}, there is nothing to replay.", backup.uuid, until_gtid);
+                return Ok(());
+            }
+
+            let mut stop = None;
+            for (index, path) in files.iter().enumerate() {
+                // Indexed files tell which transactions they hold without reading them.
+                if let Some(binlog) = indexed.iter().find(|binlog| Path::new(&binlog.path) == path) {
+                    let gtids = binlog.gtids.as_ref().and_then(|gtids| GtidSet::from_str(gtids).ok()).unwrap_or_default();
+                    if !gtids.contains(&source, transaction) {
+                        continue;
+                    }
+                }
+                let mut gtid_stop = GtidStop::new(&source, transaction);
+                read_binlog(&command_path, path, |line| gtid_stop.parse_line(line)).await?;
+                if gtid_stop.found {
+                    stop = Some((index, gtid_stop.stop_position));
+                    break;
+                }
+            }
+            let Some((index, stop_position)) = stop else {
+                return Err(format!("Transaction {} is not in any binary log archived after backup {}.", until_gtid, backup.uuid).into());
+            };
+            files.truncate(index + 1);
+            if let Some(stop_position) = stop_position {
+                cmd.arg(format!("--stop-position={}", stop_position));
+            }
+        }
+
+        // A mysqldump only holds a single database, so only replay changes made to it.
+        if backup.backup_type == 0 {
+            if let Some(database) = &backup.database_name {
+                cmd.arg(format!("--database={}", database));
+                if let Some(target_database) = options.target_database.as_ref().filter(|target| *target != database) {
+                    cmd.arg(format!("--rewrite-db={}->{}", database, target_database));
+                }
+            }
+        }
+
+        if files.is_empty() {
+            info!("No archived binary logs to replay for {}.", backup.uuid);
+            return Ok(());
+        }
+        info!("Replaying {} binary log(s) on top of {}", files.len(), backup.uuid);
+        cmd.args(&files);
+
+        let defaults = self.get_defaults_file().await?;
+        let mut binlog_child = cmd.stdout(Stdio::piped()).kill_on_drop(true).spawn()?;
+        let binlog_stdout: Stdio = binlog_child.stdout.take().unwrap().try_into()?;
+        let mysql_status = Command::new(which("mysql")?)
+            .arg(format!("--defaults-file={}", defaults.path().to_str().unwrap()))
+            .stdin(binlog_stdout)
+            .stdout(Stdio::null())
+            .status()
+            .await?;
+        let binlog_status = binlog_child.wait().await?;
+        if !binlog_status.success() {
+            return Err(format!("mysqlbinlog exited with {}.", binlog_status).into());
+        }
+        if !mysql_status.success() {
+            return Err(format!("mysql exited with {} while replaying binary logs.", mysql_status).into());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_dump_header() {
+        let header = r#"-- MySQL dump 10.13  Distrib 8.0.36, for Linux (x86_64)
+SET @@SESSION.SQL_LOG_BIN= 0;
+
+--
+-- GTID state at the beginning of the backup
+--
+
+SET @@GLOBAL.GTID_PURGED=/*!80000 '+'*/ 'aaaa:1-10,
+bbbb:1-2';
+
+--
+-- Position to start replication or point-in-time recovery from
+--
+
+-- CHANGE REPLICATION SOURCE TO SOURCE_LOG_FILE='binlog.000003', SOURCE_LOG_POS=157;
+"#;
+        let coordinates = parse_dump_header(header).unwrap();
+        assert_eq!(coordinates.file, "binlog.000003");
+        assert_eq!(coordinates.position, 157);
+        assert_eq!(coordinates.gtid_executed.as_deref(), Some("aaaa:1-10,bbbb:1-2"));
+
+        let legacy = "-- CHANGE MASTER TO MASTER_LOG_FILE='mysql-bin.000001', MASTER_LOG_POS=42;\n";
+        let coordinates = parse_dump_header(legacy).unwrap();
+        assert_eq!(coordinates.file, "mysql-bin.000001");
+        assert_eq!(coordinates.position, 42);
+        assert_eq!(coordinates.gtid_executed, None);
+
+        assert!(parse_dump_header("-- MySQL dump 10.13\n").is_none());
+    }
+
+    #[test]
+    fn test_parse_xtrabackup_binlog_info() {
+        let coordinates = parse_xtrabackup_binlog_info("binlog.000003\t157\taaaa:1-10,\nbbbb:1-2\n").unwrap();
+        assert_eq!(coordinates.file, "binlog.000003");
+        assert_eq!(coordinates.position, 157);
+        assert_eq!(coordinates.gtid_executed.as_deref(), Some("aaaa:1-10,bbbb:1-2"));
+
+        let coordinates = parse_xtrabackup_binlog_info("binlog.000003\t157\n").unwrap();
+        assert_eq!(coordinates.gtid_executed, None);
+    }
+
+    #[test]
+    fn test_parse_xtrabackup_info() {
+        let contents = "tool_name = xtrabackup\nbinlog_pos = filename 'binlog.000003', position '157', GTID of the last change 'aaaa:1-10'\n";
+        let coordinates = parse_xtrabackup_info(contents).unwrap();
+        assert_eq!(coordinates.file, "binlog.000003");
+        assert_eq!(coordinates.position, 157);
+        assert_eq!(coordinates.gtid_executed.as_deref(), Some("aaaa:1-10"));
+
+        let coordinates = parse_xtrabackup_info("binlog_pos = filename 'binlog.000003', position '157'\n").unwrap();
+        assert_eq!(coordinates.gtid_executed, None);
+        assert!(parse_xtrabackup_info("binlog_pos = \n").is_none());
+    }
+
+    #[test]
+    fn test_parse_binlog_output() {
+        let output = r#"# at 4
+#240416 18:08:21 server id 1  end_log_pos 126 CRC32 0x1c2a7a8b 	Start: binlog v 4, server v 8.0.36 created 240416 18:08:21
+# at 126
+#240416 18:08:21 server id 1  end_log_pos 197 CRC32 0x5b1b0f9c 	Previous-GTIDs
+# aaaa:1-10,
+# bbbb:1-2
+# at 197
+#240416  9:09:00 server id 1  end_log_pos 276 CRC32 0x26d6f9f1 	GTID	last_committed=0	sequence_number=1
+SET @@SESSION.GTID_NEXT= 'aaaa:11'/*!*/;
+# at 276
+#240416 18:10:00 server id 1  end_log_pos 355 CRC32 0x26d6f9f1 	GTID	last_committed=1	sequence_number=2
+SET @@SESSION.GTID_NEXT= 'aaaa:12'/*!*/;
+"#;
+        let mut summary = BinlogSummary::default();
+        for line in output.lines() {
+            summary.parse_line(line);
+        }
+
+        assert_eq!(summary.end_position, 355);
+        assert_eq!(summary.previous_gtids.to_string(), "aaaa:1-10,bbbb:1-2");
+        assert_eq!(summary.gtids.to_string(), "aaaa:11-12");
+        assert_eq!(summary.first_event_at.unwrap().to_string(), "2024-04-16 18:08:21");
+        assert_eq!(summary.last_event_at.unwrap().to_string(), "2024-04-16 18:10:00");
+    }
+
+    #[test]
+    fn test_gtid_stop() {
+        let output = r#"# at 197
+#240416  9:09:00 server id 1  end_log_pos 276 CRC32 0x26d6f9f1 	GTID	last_committed=0	sequence_number=1
+SET @@SESSION.GTID_NEXT= 'aaaa:11'/*!*/;
+# at 276
+#240416 18:09:30 server id 2  end_log_pos 355 CRC32 0x26d6f9f1 	GTID	last_committed=1	sequence_number=2
+SET @@SESSION.GTID_NEXT= 'bbbb:3'/*!*/;
+# at 355
+#240416 18:10:00 server id 1  end_log_pos 434 CRC32 0x26d6f9f1 	GTID	last_committed=2	sequence_number=3
+SET @@SESSION.GTID_NEXT= 'aaaa:12'/*!*/;
+"#;
+        let parse = |source: &str, transaction: u64| {
+            let mut stop = GtidStop::new(source, transaction);
+            for line in output.lines() {
+                stop.parse_line(line);
+            }
+            stop
+        };
+
+        // A transaction from another server after the target is not applied either.
+        let stop = parse("AAAA", 11);
+        assert!(stop.found);
+        assert_eq!(stop.stop_position, Some(276));
+        assert_eq!(parse("bbbb", 3).stop_position, Some(355));
+        let stop = parse("aaaa", 12);
+        assert!(stop.found);
+        assert_eq!(stop.stop_position, None);
+        assert!(!parse("aaaa", 13).found);
+    }
+}
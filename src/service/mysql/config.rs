@@ -1,8 +1,19 @@
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde::de::Error;
+use serde::ser::SerializeMap;
+use crate::config::{CompressionConfig, EncryptionConfig, RetentionConfig};
+use crate::storage::config::StorageConfig;
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct XtraBackupConfig {
+    /// Without `incremental_interval`, makes every run an incremental on top of the previous backup.
     pub incremental: Option<bool>,
+    /// Cron expression for incremental backups, `interval` then only takes full backups.
+    pub incremental_interval: Option<String>,
+    /// Maximum number of backups in a chain, including the full one. A longer chain forces a full backup.
+    pub max_chain_length: Option<u32>,
+    /// Take incrementals against the last full backup (differential) instead of the last incremental.
+    pub cumulative: Option<bool>,
     pub parallel_threads: Option<u8>,
     pub use_memory: Option<u32>
 }
@@ -27,10 +38,109 @@ pub struct MySQLBackupConfig {
     pub backup_type: MySQLBackupType,
     pub databases: Option<Vec<String>>,
     pub databases_exclude: Option<Vec<String>>,
-    pub interval: String
+    pub interval: String,
+    pub compression: Option<CompressionConfig>,
+    /// Overrides the global `[backup.encryption]` for this service.
+    pub encryption: Option<EncryptionConfig>,
+    /// Overrides the global `[backup.storage]` for this service.
+    pub storage: Option<StorageConfig>,
+    /// Alert when the newest backup is older than this, e.g. `26h`.
+    pub max_age: Option<String>,
+    /// Alert when a run's size differs from the average of the ones before it by more than this many percent.
+    pub max_size_deviation: Option<f64>,
+    /// How many earlier runs the size is compared with, defaults to 7.
+    pub size_window: Option<usize>,
+    /// Shell command run before the backup, a failure aborts the run.
+    pub pre_backup: Option<String>,
+    /// Shell command run after the backup whether it succeeded or not, unless `pre_backup` failed.
+    pub post_backup: Option<String>,
+    pub on_success: Option<String>,
+    pub on_failure: Option<String>,
+    /// How long each hook may take, e.g. `10m`, defaults to 5 minutes.
+    pub hook_timeout: Option<String>
+}
+
+/// A service's backups, either one unnamed job (`[service.backup]`) or several named ones,
+/// given as tables (`[service.backup.nightly]`) or as an array (`[[service.backup]]` with a `name`).
+#[derive(Debug, Clone)]
+pub enum MySQLBackupJobs {
+    Single(Box<MySQLBackupConfig>),
+    Named(Vec<(String, MySQLBackupConfig)>)
+}
+
+impl MySQLBackupJobs {
+    /// Every job along with its name, the unnamed one has none.
+    pub fn jobs(&self) -> Vec<(Option<&str>, &MySQLBackupConfig)> {
+        match self {
+            MySQLBackupJobs::Single(backup) => vec![(None, backup.as_ref())],
+            MySQLBackupJobs::Named(jobs) => jobs.iter().map(|(name, backup)| (Some(name.as_str()), backup)).collect()
+        }
+    }
+
+    pub fn job(&self, name: Option<&str>) -> Option<&MySQLBackupConfig> {
+        self.jobs().into_iter().find(|(job, _)| *job == name).map(|(_, backup)| backup)
+    }
+}
+
+impl Serialize for MySQLBackupJobs {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            MySQLBackupJobs::Single(backup) => backup.serialize(serializer),
+            MySQLBackupJobs::Named(jobs) => {
+                let mut map = serializer.serialize_map(Some(jobs.len()))?;
+                for (name, backup) in jobs {
+                    map.serialize_entry(name, backup)?;
+                }
+                map.end()
+            }
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for MySQLBackupJobs {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        // A single job is told apart from a table of jobs by its `type`, so no job may be called that.
+        let parse_job = |name: &str, value: toml::Value| {
+            if name == "type" {
+                return Err(D::Error::custom("a backup job can't be named \"type\", it is reserved for the backup type of an unnamed job"));
+            }
+            MySQLBackupConfig::deserialize(value).map_err(|error| D::Error::custom(format!("backup job {}: {}", name, error.message())))
+        };
+        match toml::Value::deserialize(deserializer)? {
+            toml::Value::Array(values) => {
+                let mut jobs: Vec<(String, MySQLBackupConfig)> = vec![];
+                for value in values {
+                    let Some(name) = value.get("name").and_then(|name| name.as_str()).map(str::to_string) else {
+                        return Err(D::Error::custom("every backup job in an array needs a name"));
+                    };
+                    if jobs.iter().any(|(job, _)| *job == name) {
+                        return Err(D::Error::custom(format!("backup job {} is defined twice", name)));
+                    }
+                    let backup = parse_job(&name, value)?;
+                    jobs.push((name, backup));
+                }
+                Ok(MySQLBackupJobs::Named(jobs))
+            }
+            toml::Value::Table(table) if !table.get("type").is_some_and(toml::Value::is_str) => {
+                let jobs = table.into_iter().map(|(name, value)| parse_job(&name, value).map(|backup| (name, backup))).collect::<Result<Vec<_>, _>>()?;
+                Ok(MySQLBackupJobs::Named(jobs))
+            }
+            value => Ok(MySQLBackupJobs::Single(Box::new(MySQLBackupConfig::deserialize(value).map_err(|error| D::Error::custom(error.message()))?)))
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct BinlogConfig {
+    /// Directory the raw binary logs are archived to, defaults to `<basedir>/binlogs/<service>`.
+    pub basedir: Option<String>,
+    /// Binary log to start archiving from when the archive is empty, defaults to the oldest one on the server.
+    pub start_file: Option<String>,
+    /// Cron expression for recording finished binary logs in the catalog.
+    pub index_interval: String
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
 pub struct MySQLConnectionConfig {
     pub host: Option<String>,
     pub port: Option<u16>,
@@ -38,5 +148,8 @@ pub struct MySQLConnectionConfig {
     pub password: Option<String>,
     pub socket: Option<String>,
     pub defaults_file: Option<String>,
-    pub backup: Option<MySQLBackupConfig>
+    pub backup: Option<MySQLBackupJobs>,
+    pub binlog: Option<BinlogConfig>,
+    /// Overrides the global retention policy rule by rule.
+    pub retention: Option<RetentionConfig>
 }
\ No newline at end of file
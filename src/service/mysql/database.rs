@@ -1,8 +1,13 @@
-use sqlx::types::chrono::NaiveDateTime;
+use std::path::Path;
+use sqlx::types::chrono::{NaiveDateTime, Utc};
 use sqlx::types::Uuid;
+use uuid::{NoContext, Timestamp};
+use serde::Serialize;
 use sqlx::FromRow;
+use crate::catalog::Catalog;
+use crate::config::CompressionCodec;
 
-#[derive(Debug, FromRow)]
+#[derive(Debug, FromRow, Serialize)]
 pub struct MysqlBackupRow {
     pub uuid: Uuid,
     pub base_uuid: Option<Uuid>, // used for xtrabackup
@@ -10,5 +15,267 @@ pub struct MysqlBackupRow {
     pub backup_type: u8, // 0 = mysqldump, 1 = xtrabackup
     pub path: String,
     pub size: i64,
-    pub created_at: NaiveDateTime
-}
\ No newline at end of file
+    pub created_at: NaiveDateTime,
+    pub database_name: Option<String>, // used for mysqldump
+    pub binlog_file: Option<String>,
+    pub binlog_position: Option<i64>,
+    pub gtid_executed: Option<String>,
+    pub service: Option<String>,
+    pub checkpoint_type: Option<String>, // used for xtrabackup
+    pub from_lsn: Option<i64>,
+    pub to_lsn: Option<i64>,
+    pub last_lsn: Option<i64>,
+    pub compression: Option<String>,
+    /// Comma separated age recipients the artifact is encrypted to.
+    pub encryption_recipients: Option<String>,
+    /// Key of the uploaded copy in the configured storage.
+    pub remote_key: Option<String>,
+    /// SHA-256 of the file, or of the manifest for directories.
+    pub checksum: Option<String>,
+    /// The run which took the backup.
+    pub run_uuid: Option<Uuid>,
+    /// The machine which took the backup, the catalog may be shared between several.
+    pub host: Option<String>,
+    /// The backup job which took it, `None` for a service's unnamed job.
+    pub job: Option<String>
+}
+
+impl MysqlBackupRow {
+    /// What kind of backup this is, as shown to users.
+    pub fn kind(&self) -> &str {
+        match (self.backup_type, self.checkpoint_type.as_deref()) {
+            (0, _) => "mysqldump",
+            (_, Some("incremental")) => "incremental",
+            _ => "full"
+        }
+    }
+
+    /// Groups backups which replace each other, retention is applied per series.
+    pub fn series(&self) -> String {
+        let series = if self.backup_type == 1 {
+            "xtrabackup".to_string()
+        } else {
+            // Tables of a `separate_tables` dump live in a directory named after the database.
+            let path = Path::new(&self.path);
+            let database = self.database_name.clone().unwrap_or_default();
+            let in_table_directory = path.parent().and_then(|parent| parent.file_name()).is_some_and(|parent| parent.to_str() == Some(database.as_str()));
+            match path.file_name().and_then(|file_name| file_name.to_str()) {
+                Some(file_name) if in_table_directory => format!("mysqldump:{}", CompressionCodec::strip_extension(file_name)),
+                _ => format!("mysqldump:{}", database)
+            }
+        };
+
+        // Each job keeps its own backups, e.g. hourly dumps never push out nightly ones.
+        match &self.job {
+            Some(job) => format!("{}:{}", job, series),
+            None => series
+        }
+    }
+
+    /// Finds every backup taken on top of the given one, directly or through other incrementals.
+    pub async fn find_dependents(catalog: &dyn Catalog, uuid: Uuid) -> Result<Vec<MysqlBackupRow>, sqlx::Error> {
+        let mut dependents: Vec<MysqlBackupRow> = vec![];
+        let mut pending = vec![uuid];
+        while let Some(base_uuid) = pending.pop() {
+            let children = catalog.find_children(base_uuid).await?;
+            for child in children {
+                if child.uuid != uuid && !dependents.iter().any(|dependent| dependent.uuid == child.uuid) {
+                    pending.push(child.uuid);
+                    dependents.push(child);
+                }
+            }
+        }
+        Ok(dependents)
+    }
+
+    /// Checks that every incremental in the chain starts at the LSN its base ended at, links recorded before LSNs were tracked can't be checked and pass.
+    pub fn validate_chain(chain: &[MysqlBackupRow]) -> Result<(), String> {
+        if let Some(full_backup) = chain.first() {
+            if full_backup.checkpoint_type.as_ref().is_some_and(|checkpoint_type| checkpoint_type == "incremental") {
+                return Err(format!("Chain starts at {}, which is an incremental backup.", full_backup.uuid));
+            }
+        }
+        for pair in chain.windows(2) {
+            let (base, incremental) = (&pair[0], &pair[1]);
+            match (base.to_lsn, incremental.from_lsn) {
+                (Some(to_lsn), Some(from_lsn)) if to_lsn != from_lsn => {
+                    return Err(format!("Backup {} starts at LSN {} but its base {} ends at LSN {}.", incremental.uuid, from_lsn, base.uuid, to_lsn));
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// Walks `base_uuid` back to the full backup and returns the chain, starting with the full backup.
+    pub async fn find_chain(catalog: &dyn Catalog, uuid: Uuid) -> Result<Vec<MysqlBackupRow>, Box<dyn std::error::Error>> {
+        let mut chain = vec![];
+        let mut next = Some(uuid);
+        while let Some(uuid) = next {
+            if chain.iter().any(|backup: &MysqlBackupRow| backup.uuid == uuid) {
+                return Err(format!("Backup chain contains a cycle at {}.", uuid).into());
+            }
+
+            let backup = match catalog.find_backup(uuid).await? {
+                Some(backup) => backup,
+                None => return Err(format!("Backup {} is missing from the catalog, the chain is broken.", uuid).into())
+            };
+            next = backup.base_uuid;
+            chain.push(backup);
+        }
+        chain.reverse();
+        Ok(chain)
+    }
+}
+
+#[cfg(test)]
+impl MysqlBackupRow {
+    /// A full xtrabackup of `mysql-r1` taken `uuid` hours into 2024-04-16, tests override what they care about.
+    pub fn test_default(uuid: u128) -> MysqlBackupRow {
+        MysqlBackupRow {
+            uuid: Uuid::from_u128(uuid),
+            base_uuid: None,
+            backup_type: 1,
+            path: format!("/srv/{}", uuid),
+            size: 1024,
+            created_at: sqlx::types::chrono::NaiveDate::from_ymd_opt(2024, 4, 16).unwrap().and_hms_opt(0, 0, 0).unwrap() + std::time::Duration::from_secs(uuid as u64 * 3600),
+            database_name: None,
+            binlog_file: None,
+            binlog_position: None,
+            gtid_executed: None,
+            service: Some("mysql-r1".to_string()),
+            checkpoint_type: Some("full-backuped".to_string()),
+            from_lsn: None,
+            to_lsn: None,
+            last_lsn: None,
+            compression: None,
+            encryption_recipients: None,
+            remote_key: None,
+            checksum: None,
+            run_uuid: None,
+            host: None,
+            job: None
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RunTrigger {
+    Cron,
+    Manual
+}
+
+impl RunTrigger {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RunTrigger::Cron => "cron",
+            RunTrigger::Manual => "manual"
+        }
+    }
+}
+
+#[allow(dead_code)] // mirrors the runs table, not every column is used yet
+#[derive(Debug, FromRow, Serialize)]
+pub struct RunRow {
+    pub uuid: Uuid,
+    pub service: String,
+    pub trigger: String, // cron or manual
+    pub started_at: NaiveDateTime,
+    pub finished_at: Option<NaiveDateTime>,
+    pub status: String, // running, succeeded or failed
+    pub error: Option<String>,
+    pub stderr: Option<String>,
+    pub host: Option<String>,
+    pub job: Option<String>
+}
+
+impl RunRow {
+    /// A run which starts now.
+    pub fn start(service: &str, job: Option<&str>, trigger: RunTrigger, host: &str) -> RunRow {
+        RunRow {
+            uuid: Uuid::new_v7(Timestamp::now(NoContext)),
+            service: service.to_string(),
+            job: job.map(str::to_string),
+            trigger: trigger.as_str().to_string(),
+            started_at: Utc::now().naive_utc(),
+            finished_at: None,
+            status: "running".to_string(),
+            error: None,
+            stderr: None,
+            host: Some(host.to_string())
+        }
+    }
+
+    /// Marks the run as ended, `error` and `stderr` are only set for failed runs.
+    pub fn finish(&mut self, error: Option<String>, stderr: Option<String>) {
+        self.finished_at = Some(Utc::now().naive_utc());
+        self.status = if error.is_some() { "failed" } else { "succeeded" }.to_string();
+        self.error = error;
+        self.stderr = stderr;
+    }
+}
+
+#[allow(dead_code)] // mirrors the binlogs table, not every column is used yet
+#[derive(Debug, FromRow)]
+pub struct BinlogRow {
+    pub uuid: Uuid,
+    pub service: String,
+    pub file_name: String,
+    pub path: String,
+    pub start_position: i64,
+    pub end_position: i64,
+    pub first_event_at: Option<NaiveDateTime>,
+    pub last_event_at: Option<NaiveDateTime>,
+    pub previous_gtids: Option<String>,
+    pub gtids: Option<String>,
+    pub size: i64,
+    pub created_at: NaiveDateTime,
+    pub host: Option<String>
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+    use crate::catalog::sqlite::SqliteCatalog;
+    use crate::config::CatalogConfig;
+
+    fn backup(uuid: u128, base_uuid: Option<u128>) -> MysqlBackupRow {
+        MysqlBackupRow {
+            base_uuid: base_uuid.map(Uuid::from_u128),
+            checkpoint_type: Some(if base_uuid.is_some() { "incremental" } else { "full-backuped" }.to_string()),
+            ..MysqlBackupRow::test_default(uuid)
+        }
+    }
+
+    #[test]
+    fn test_validate_chain() {
+        let link = |uuid: u128, base_uuid: Option<u128>, from_lsn: Option<i64>, to_lsn: Option<i64>| MysqlBackupRow { from_lsn, to_lsn, ..backup(uuid, base_uuid) };
+        assert!(MysqlBackupRow::validate_chain(&[link(1, None, Some(0), Some(100)), link(2, Some(1), Some(100), Some(200))]).is_ok());
+        assert!(MysqlBackupRow::validate_chain(&[link(1, None, Some(0), Some(100)), link(2, Some(1), Some(150), Some(200))]).unwrap_err().contains("starts at LSN 150"));
+        assert!(MysqlBackupRow::validate_chain(&[link(1, None, Some(0), None), link(2, Some(1), Some(100), Some(200))]).is_ok());
+        assert!(MysqlBackupRow::validate_chain(&[link(1, None, Some(0), Some(100)), link(2, Some(1), None, Some(200))]).is_ok());
+        assert!(MysqlBackupRow::validate_chain(&[link(2, Some(1), Some(100), Some(200))]).is_err());
+
+        // Chains taken before LSNs were recorded still restore.
+        assert!(MysqlBackupRow::validate_chain(&[backup(1, None), backup(2, Some(1)), backup(3, Some(2))]).is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_find_chain() {
+        let dir = tempdir().unwrap();
+        let catalog = SqliteCatalog::connect(&CatalogConfig::default(), dir.path()).await.unwrap();
+        catalog.insert_backup(&backup(1, None)).await.unwrap();
+        catalog.insert_backup(&backup(2, Some(1))).await.unwrap();
+        catalog.insert_backup(&backup(3, Some(2))).await.unwrap();
+
+        let chain = MysqlBackupRow::find_chain(&catalog, Uuid::from_u128(3)).await.unwrap();
+        assert_eq!(chain.iter().map(|backup| backup.uuid.as_u128()).collect::<Vec<_>>(), vec![1, 2, 3]);
+        assert_eq!(MysqlBackupRow::find_chain(&catalog, Uuid::from_u128(1)).await.unwrap().len(), 1);
+
+        // A base which is gone from the catalog breaks the chain.
+        catalog.insert_backup(&backup(5, Some(4))).await.unwrap();
+        let error = MysqlBackupRow::find_chain(&catalog, Uuid::from_u128(5)).await.unwrap_err();
+        assert!(error.to_string().contains("is missing from the catalog"));
+    }
+}
@@ -1,6 +0,0 @@
-use async_trait::async_trait;
-
-#[async_trait]
-pub trait MySqlBackup {
-    async fn dump();
-}
\ No newline at end of file
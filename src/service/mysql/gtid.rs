@@ -0,0 +1,129 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+
+/// A MySQL GTID set, e.g. `3E11FA47-71CA-11E1-9E33-C80AA9429562:1-5:7,uuid2:1-3`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct GtidSet {
+    intervals: BTreeMap<String, Vec<(u64, u64)>>,
+}
+
+impl GtidSet {
+    pub fn is_empty(&self) -> bool {
+        self.intervals.is_empty()
+    }
+
+    /// Adds a single transaction to the set, merging it into the adjacent intervals.
+    pub fn add(&mut self, source: &str, transaction: u64) {
+        self.add_interval(source, transaction, transaction);
+    }
+
+    pub fn union(&mut self, other: &GtidSet) {
+        for (source, intervals) in &other.intervals {
+            for (start, end) in intervals {
+                self.add_interval(source, *start, *end);
+            }
+        }
+    }
+
+    pub fn contains(&self, source: &str, transaction: u64) -> bool {
+        match self.intervals.get(&source.to_lowercase()) {
+            Some(intervals) => intervals.iter().any(|(start, end)| *start <= transaction && transaction <= *end),
+            None => false
+        }
+    }
+
+    fn add_interval(&mut self, source: &str, start: u64, end: u64) {
+        let intervals = self.intervals.entry(source.to_lowercase()).or_default();
+        intervals.push((start, end));
+        intervals.sort();
+
+        // Merge overlapping and adjacent intervals.
+        let mut merged: Vec<(u64, u64)> = Vec::with_capacity(intervals.len());
+        for (start, end) in intervals.drain(..) {
+            match merged.last_mut() {
+                Some(last) if start <= last.1 + 1 => last.1 = last.1.max(end),
+                _ => merged.push((start, end))
+            }
+        }
+        *intervals = merged;
+    }
+}
+
+/// Parses a single GTID such as `uuid:23`.
+pub fn parse_gtid(gtid: &str) -> Result<(String, u64), String> {
+    match gtid.trim().rsplit_once(':') {
+        Some((source, transaction)) => {
+            let transaction = transaction.parse::<u64>().map_err(|_| format!("Invalid GTID transaction in {}", gtid))?;
+            Ok((source.to_lowercase(), transaction))
+        }
+        None => Err(format!("Invalid GTID {}", gtid))
+    }
+}
+
+impl FromStr for GtidSet {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let mut set = GtidSet::default();
+        for part in value.split(',').map(|part| part.trim()).filter(|part| !part.is_empty()) {
+            let mut pieces = part.split(':');
+            let source = pieces.next().unwrap_or_default();
+            for interval in pieces {
+                let (start, end) = match interval.split_once('-') {
+                    Some((start, end)) => (start, end),
+                    None => (interval, interval)
+                };
+                let start = start.parse::<u64>().map_err(|_| format!("Invalid GTID interval {}", interval))?;
+                let end = end.parse::<u64>().map_err(|_| format!("Invalid GTID interval {}", interval))?;
+                set.add_interval(source, start, end);
+            }
+        }
+        Ok(set)
+    }
+}
+
+impl fmt::Display for GtidSet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let parts = self.intervals.iter()
+            .map(|(source, intervals)| {
+                let intervals = intervals.iter()
+                    .map(|(start, end)| if start == end { format!("{}", start) } else { format!("{}-{}", start, end) })
+                    .collect::<Vec<String>>();
+                format!("{}:{}", source, intervals.join(":"))
+            })
+            .collect::<Vec<String>>();
+        write!(f, "{}", parts.join(","))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_and_format() {
+        let set = GtidSet::from_str("3E11FA47-71CA-11E1-9E33-C80AA9429562:1-5:7, aaaa:3").unwrap();
+        assert_eq!(set.to_string(), "3e11fa47-71ca-11e1-9e33-c80aa9429562:1-5:7,aaaa:3");
+        assert!(GtidSet::from_str("").unwrap().is_empty());
+        assert!(GtidSet::from_str("aaaa:x").is_err());
+    }
+
+    #[test]
+    fn test_add_merges_intervals() {
+        let mut set = GtidSet::default();
+        set.add("aaaa", 1);
+        set.add("aaaa", 3);
+        assert_eq!(set.to_string(), "aaaa:1:3");
+        set.add("aaaa", 2);
+        assert_eq!(set.to_string(), "aaaa:1-3");
+        assert!(set.contains("AAAA", 2));
+        assert!(!set.contains("aaaa", 4));
+    }
+
+    #[test]
+    fn test_parse_gtid() {
+        assert_eq!(parse_gtid("AAAA:12").unwrap(), ("aaaa".to_string(), 12));
+        assert!(parse_gtid("aaaa").is_err());
+    }
+}
@@ -1,7 +1,10 @@
 pub mod config;
 pub mod mysql_service;
 pub mod database;
+pub mod binlog;
+mod gtid;
 mod mysql_defaults;
 mod mysqldump;
-mod dump;
-mod xtrabackup;
\ No newline at end of file
+pub mod restore;
+pub mod verify;
+pub mod xtrabackup;
\ No newline at end of file
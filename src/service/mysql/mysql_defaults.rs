@@ -37,6 +37,8 @@ impl MySqlDefaultsReader for MySqlConnectOptions {
 mod tests {
     use super::*;
     use std::io::Write;
+    use std::path::PathBuf;
+    use sqlx::ConnectOptions;
     use tempfile::NamedTempFile;
 
     #[test]
@@ -54,11 +56,11 @@ mod tests {
         let options = MySqlConnectOptions::from_defaults_file(file.path()).unwrap();
 
         // Assert the expected values
-        assert_eq!(options.host(), Some("localhost"));
-        assert_eq!(options.port(), Some(3306));
-        assert_eq!(options.username(), Some("testuser"));
-        assert_eq!(options.password(), Some("testpass"));
-        assert_eq!(options.socket(), Some("/tmp/mysql.sock"));
+        assert_eq!(options.get_host(), "localhost");
+        assert_eq!(options.get_port(), 3306);
+        assert_eq!(options.get_username(), "testuser");
+        assert_eq!(options.to_url_lossy().password(), Some("testpass"));
+        assert_eq!(options.get_socket(), Some(&PathBuf::from("/tmp/mysql.sock")));
     }
 
     #[test]
@@ -71,12 +73,12 @@ mod tests {
         // Call the from_defaults_file function
         let options = MySqlConnectOptions::from_defaults_file(file.path()).unwrap();
 
-        // Assert the expected values
-        assert_eq!(options.host(), Some("localhost"));
-        assert_eq!(options.port(), None);
-        assert_eq!(options.username(), None);
-        assert_eq!(options.password(), None);
-        assert_eq!(options.socket(), None);
+        // Assert the expected values, falling back to the sqlx defaults
+        assert_eq!(options.get_host(), "localhost");
+        assert_eq!(options.get_port(), 3306);
+        assert_eq!(options.get_username(), "root");
+        assert_eq!(options.to_url_lossy().password(), None);
+        assert_eq!(options.get_socket(), None);
     }
 
     #[test]
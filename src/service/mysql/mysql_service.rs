@@ -1,12 +1,13 @@
 use std::any::Any;
-use std::path::PathBuf;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
 use std::sync::{Arc};
 use std::time::Duration;
 use async_trait::async_trait;
-use log::{error, info, warn};
+use log::{debug, error, info, warn};
 use tokio_cron_scheduler::{Job, JobScheduler};
-use crate::service::mysql::config::{MySQLBackupType, MySQLConnectionConfig};
+use crate::service::mysql::config::{MySQLBackupConfig, MySQLBackupType, MySQLConnectionConfig, XtraBackupConfig};
 use crate::service::service::{ServiceScheduler, Service};
 use cron::Schedule;
 use tempfile::NamedTempFile;
@@ -14,40 +15,139 @@ use ini::Ini;
 use sqlx::types::chrono::Utc;
 use tokio::fs;
 use tokio::sync::Mutex;
-use crate::config::BackupConfig;
-use crate::DB_POOL;
-use crate::service::mysql::database::MysqlBackupRow;
+use tokio::task::JoinHandle;
+use tokio::time::sleep;
+use uuid::Uuid;
+use age::x25519::{Identity, Recipient};
+use crate::config::{BackupConfig, EncryptionConfig, RetentionConfig};
+use crate::encryption::read_identities;
+use crate::hook::{run_hook, Hook, DEFAULT_HOOK_TIMEOUT};
+use crate::notification::{notify, Notification};
+use crate::notification::config::NotificationsConfig;
+use crate::retention::expired_chains;
+use crate::storage::config::StorageConfig;
+use crate::storage::create_storage;
+use crate::utils::{parse_duration, ToolError};
+use crate::CATALOG;
+use crate::catalog::Catalog;
+use crate::service::mysql::binlog::BinlogArchiver;
+use crate::service::mysql::database::{MysqlBackupRow, RunRow, RunTrigger};
 use crate::service::mysql::mysqldump::MySqlDumpRunner;
-use crate::service::mysql::xtrabackup::XtraBackupRunner;
+use crate::service::mysql::xtrabackup::{XtraBackupMode, XtraBackupRunner};
 
+/// Files left behind in an xtrabackup directory when the uploaded copy replaces the local one.
+const KEPT_LOCAL_FILES: [&str; 2] = ["xtrabackup_checkpoints", "xtrabackup_info"];
+
+/// The message of a failed step and what the tool behind it wrote to stderr, as recorded with the run.
+fn describe_error(error: Box<dyn std::error::Error>) -> (String, Option<String>) {
+    let stderr = error.downcast_ref::<ToolError>().map(|error| error.stderr.clone()).filter(|stderr| !stderr.is_empty());
+    (error.to_string(), stderr)
+}
+
+/// Whether the path still holds the backup itself, rather than nothing or only the files `store_backup` keeps.
+async fn is_local_copy(path: &Path) -> Result<bool, std::io::Error> {
+    if path.is_file() {
+        return Ok(true);
+    }
+    if !path.is_dir() {
+        return Ok(false);
+    }
+    let mut entries = fs::read_dir(path).await?;
+    while let Some(entry) = entries.next_entry().await? {
+        if !KEPT_LOCAL_FILES.iter().any(|file_name| entry.file_name() == *file_name) {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+#[derive(Clone)]
 pub struct MySQLService {
+    pub name: String,
     pub backup_config: BackupConfig,
     pub config: MySQLConnectionConfig,
-    pub running: Arc<Mutex<bool>>,
+    pub notifications: Option<NotificationsConfig>,
+    /// The backup job this runs, `None` for the unnamed one.
+    pub job: Option<String>,
+    /// The settings of that job, `None` when the service has none or only named ones.
+    pub backup: Option<MySQLBackupConfig>,
+    /// The jobs which are running, shared between every job of the service.
+    pub running: Arc<Mutex<HashSet<Option<String>>>>,
+    /// Background tasks, stopped on shutdown.
+    pub tasks: Arc<Mutex<Vec<JoinHandle<()>>>>,
 }
 
 impl MySQLService {
-    pub fn new(config: MySQLConnectionConfig, backup_config: BackupConfig) -> MySQLService {
-        return MySQLService {
+    pub fn new(name: &str, config: MySQLConnectionConfig, backup_config: BackupConfig, notifications: Option<NotificationsConfig>) -> MySQLService {
+        MySQLService {
+            name: name.to_string(),
             backup_config,
+            backup: config.backup.as_ref().and_then(|backup| backup.job(None)).cloned(),
             config,
-            running: Arc::new(Mutex::new(false)),
-        };
+            notifications,
+            job: None,
+            running: Arc::new(Mutex::new(HashSet::new())),
+            tasks: Arc::new(Mutex::new(vec![])),
+        }
     }
 
-    pub async fn try_set_running(&self) -> bool {
-        let mut running = self.running.lock().await;
-        if *running {
-            false
-        } else {
-            *running = true;
-            true
+    /// The names of the service's named backup jobs.
+    pub fn job_names(&self) -> Vec<String> {
+        self.config.backup.iter().flat_map(|backup| backup.jobs()).filter_map(|(job, _)| job.map(str::to_string)).collect()
+    }
+
+    /// The service as it runs one of its backup jobs, `None` picking the unnamed one.
+    pub fn job(&self, name: Option<&str>) -> Option<MySQLService> {
+        let backup = self.config.backup.as_ref()?.job(name)?;
+        Some(MySQLService {
+            job: name.map(str::to_string),
+            backup: Some(backup.clone()),
+            ..self.clone()
+        })
+    }
+
+    /// The service as it runs each of its backup jobs.
+    pub fn job_services(&self) -> Vec<MySQLService> {
+        self.config.backup.iter().flat_map(|backup| backup.jobs()).filter_map(|(job, _)| self.job(job)).collect()
+    }
+
+    /// Like `job`, explaining what is wrong when there is no such job.
+    pub fn find_job(&self, name: Option<&str>) -> Result<MySQLService, String> {
+        if let Some(service) = self.job(name) {
+            return Ok(service);
+        }
+        let job_names = self.job_names();
+        match name {
+            Some(name) => Err(format!("Service {} has no backup job {}.", self.name, name)),
+            None if job_names.is_empty() => Err(format!("Service {} has no backup configured.", self.name)),
+            None => Err(format!("Service {} has several backup jobs, pick one of: {}.", self.name, job_names.join(", ")))
         }
     }
 
+    /// The service as it runs the job which took the backup, or as it is once that job is gone.
+    pub fn for_backup(&self, backup: &MysqlBackupRow) -> MySQLService {
+        self.job(backup.job.as_deref()).unwrap_or_else(|| self.clone())
+    }
+
+    /// The service with the job in its name, for logs.
+    pub fn label(&self) -> String {
+        match &self.job {
+            Some(job) => format!("{}.{}", self.name, job),
+            None => self.name.clone()
+        }
+    }
+
+    pub async fn try_set_running(&self) -> bool {
+        self.running.lock().await.insert(self.job.clone())
+    }
+
     pub async fn set_running(&self, value: bool) {
         let mut running = self.running.lock().await;
-        *running = value;
+        if value {
+            running.insert(self.job.clone());
+        } else {
+            running.remove(&self.job);
+        }
     }
 
     pub async fn get_defaults_file(&self) -> Result<NamedTempFile, Box<dyn std::error::Error>> {
@@ -77,82 +177,508 @@ impl MySQLService {
         }
         Ok(file)
     }
+
+    /// The global retention policy with this service's overrides applied.
+    pub fn retention_policy(&self) -> RetentionConfig {
+        let policy = self.backup_config.retention_policy();
+        match &self.config.retention {
+            Some(retention) => policy.merge(retention),
+            None => policy
+        }
+    }
+
+    /// The encryption settings, a service level section replaces the global one.
+    pub fn encryption(&self) -> Option<&EncryptionConfig> {
+        self.backup.as_ref()
+            .and_then(|backup| backup.encryption.as_ref())
+            .or(self.backup_config.encryption.as_ref())
+    }
+
+    /// The recipients new artifacts are encrypted to, empty when encryption is disabled.
+    pub fn encryption_recipients(&self) -> Result<Vec<Recipient>, Box<dyn std::error::Error>> {
+        match self.encryption() {
+            Some(encryption) => Ok(encryption.parse_recipients()?),
+            None => Ok(vec![])
+        }
+    }
+
+    /// The identities used to read encrypted artifacts back, `identity_file` wins over the configured one.
+    pub async fn encryption_identities(&self, identity_file: Option<&Path>) -> Result<Vec<Identity>, Box<dyn std::error::Error>> {
+        match (identity_file, self.encryption()) {
+            (Some(identity_file), _) => Ok(read_identities(identity_file).await?),
+            (None, Some(encryption)) if encryption.identity_file.is_some() => Ok(encryption.read_identities().await?),
+            _ => Ok(vec![])
+        }
+    }
+
+    /// The storage artifacts are uploaded to, a service level section replaces the global one.
+    pub fn storage_config(&self) -> Option<&StorageConfig> {
+        self.backup.as_ref()
+            .and_then(|backup| backup.storage.as_ref())
+            .or(self.backup_config.storage.as_ref())
+    }
+
+    /// The storage key of an artifact, its path below `basedir` under the service name.
+    fn storage_key(&self, path: &Path) -> String {
+        let relative = path.strip_prefix(&self.backup_config.basedir).unwrap_or(path);
+        let parts = relative.iter().filter_map(|part| part.to_str()).filter(|part| *part != "/").collect::<Vec<&str>>();
+        format!("{}/{}", self.name, parts.join("/"))
+    }
+
+    /// Uploads a backup already in the catalog when storage is configured and records where it went, the local copy is
+    /// only removed once the catalog knows about the upload.
+    pub async fn store_backup(&self, catalog: &dyn Catalog, uuid: Uuid, path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+        let Some(storage_config) = self.storage_config() else { return Ok(()) };
+        let storage = create_storage(storage_config)?;
+        let key = self.storage_key(path);
+        info!("Uploading {} to {}", path.to_str().unwrap(), key);
+        storage.upload(path, &key).await?;
+        catalog.set_remote_key(uuid, &key).await?;
+
+        if storage_config.delete_local.unwrap_or(false) {
+            if path.is_dir() {
+                // Keep the checkpoints, they are all xtrabackup needs for the backup to stay an incremental base.
+                let mut entries = fs::read_dir(path).await?;
+                while let Some(entry) = entries.next_entry().await? {
+                    if KEPT_LOCAL_FILES.iter().any(|file_name| entry.file_name() == *file_name) {
+                        continue;
+                    }
+                    if entry.file_type().await?.is_dir() {
+                        fs::remove_dir_all(entry.path()).await?;
+                    } else {
+                        fs::remove_file(entry.path()).await?;
+                    }
+                }
+            } else {
+                fs::remove_file(path).await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns where the backup can be read from, downloading it into `download_dir` when only the uploaded copy is left.
+    pub async fn fetch_backup(&self, backup: &MysqlBackupRow, download_dir: &Path) -> Result<PathBuf, Box<dyn std::error::Error>> {
+        let path = PathBuf::from(&backup.path);
+        if is_local_copy(&path).await? {
+            return Ok(path);
+        }
+        if backup.remote_key.is_none() {
+            return Err(format!("Backup {} does not exist at {}.", backup.uuid, backup.path).into());
+        }
+        self.download_backup(backup, download_dir).await
+    }
+
+    /// Downloads the uploaded copy of the backup into `download_dir`, returning where it ended up.
+    pub async fn download_backup(&self, backup: &MysqlBackupRow, download_dir: &Path) -> Result<PathBuf, Box<dyn std::error::Error>> {
+        let (Some(remote_key), Some(storage_config)) = (&backup.remote_key, self.storage_config()) else {
+            return Err(format!("Backup {} has no uploaded copy in a configured storage.", backup.uuid).into());
+        };
+
+        // Dumps keep their file name, so the extensions still tell how to read them.
+        let path = PathBuf::from(&backup.path);
+        let target = match path.file_name() {
+            Some(file_name) if backup.backup_type == 0 => download_dir.join(file_name),
+            _ => download_dir.join(backup.uuid.to_string())
+        };
+        info!("Downloading backup {} from {}", backup.uuid, remote_key);
+        let storage = create_storage(storage_config)?;
+        storage.download(remote_key, &target).await?;
+        Ok(target)
+    }
+
+    /// Removes this service's backups the retention policy no longer keeps, both from disk and from the catalog.
+    pub async fn prune_backups(&self) -> Result<(), Box<dyn std::error::Error>> {
+        let catalog = CATALOG.get().unwrap().as_ref();
+        let expired = self.expired_backups(catalog).await?;
+        self.remove_backups(catalog, &expired).await?;
+        if !expired.is_empty() {
+            notify(self.notifications.as_ref(), &Notification::retention_deleted(&self.name, self.job.as_deref(), catalog.host(), &expired)).await;
+        }
+        Ok(())
+    }
+
+    /// Finds this service's backups the retention policy no longer keeps.
+    pub async fn expired_backups(&self, catalog: &dyn Catalog) -> Result<Vec<MysqlBackupRow>, Box<dyn std::error::Error>> {
+        let policy = self.retention_policy();
+        if policy.is_empty() {
+            return Ok(vec![]);
+        }
+
+        let backups = catalog.find_local_backups(&self.name).await?;
+
+        // The policy applies to each series on its own, so one database's dumps never push out another's.
+        let mut series: HashMap<String, Vec<MysqlBackupRow>> = HashMap::new();
+        for backup in backups {
+            series.entry(backup.series()).or_default().push(backup);
+        }
+        let now = Utc::now().naive_utc();
+        let mut expired = vec![];
+        for (_, backups) in series {
+            let created_at = backups.iter().map(|backup| backup.created_at).collect::<Vec<_>>();
+            let mut expired_indices = policy.expired(&created_at, now);
+
+            // Incremental xtrabackups are useless without their base, so a chain only goes once all of it expired.
+            let links = backups.iter().map(|backup| (backup.uuid, backup.base_uuid)).collect::<Vec<_>>();
+            let chain_indices = expired_chains(&links, &expired_indices);
+            if chain_indices.len() != expired_indices.len() {
+                debug!("Keeping {} expired backup(s) of {} which are part of a live chain.", expired_indices.len() - chain_indices.len(), self.name);
+            }
+            expired_indices = chain_indices;
+            expired.extend(backups.into_iter().enumerate().filter(|(index, _)| expired_indices.contains(index)).map(|(_, backup)| backup));
+        }
+        expired.sort_by_key(|backup| backup.created_at);
+        Ok(expired)
+    }
+
+    /// Removes the given backups from disk, storage and the catalog, a row only goes away once every copy of it is gone.
+    pub async fn remove_backups(&self, catalog: &dyn Catalog, backups: &[MysqlBackupRow]) -> Result<(), Box<dyn std::error::Error>> {
+        // The files go first, so the catalog is never locked while waiting on disks or storage.
+        let mut removed = vec![];
+        let mut failure = None;
+        for backup in backups {
+            if let Err(error) = self.remove_backup_files(backup).await {
+                failure = Some(format!("Failed to remove backup {}: {}", backup.uuid, error));
+                break;
+            }
+            removed.push(backup.uuid);
+        }
+
+        // Then every row whose files are gone, a later prune retries the rest.
+        catalog.delete_backups(&removed).await?;
+        match failure {
+            Some(failure) => Err(failure.into()),
+            None => Ok(())
+        }
+    }
+
+    /// Removes the backup's file or directory and its uploaded copy.
+    async fn remove_backup_files(&self, backup: &MysqlBackupRow) -> Result<(), Box<dyn std::error::Error>> {
+        // Jobs may upload to storages of their own.
+        let storage = match self.for_backup(backup).storage_config() {
+            Some(storage_config) => Some(create_storage(storage_config)?),
+            None => None
+        };
+        info!("Removing backup {} at {}", backup.uuid, backup.path);
+        let path = PathBuf::from_str(&backup.path).unwrap();
+        if path.is_file() {
+            fs::remove_file(path).await?;
+        } else if path.is_dir() {
+            fs::remove_dir_all(path).await?;
+        } else {
+            warn!("Backup {} was already removed from disk.", backup.path);
+        }
+
+        if let Some(remote_key) = &backup.remote_key {
+            match &storage {
+                Some(storage) => storage.delete(remote_key).await?,
+                None => warn!("Backup {} is stored at {}, but no storage is configured to remove it from.", backup.uuid, remote_key)
+            }
+        }
+        Ok(())
+    }
 }
 
 #[async_trait]
 impl Service for MySQLService {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn jobs(&self) -> Vec<String> {
+        self.job_names()
+    }
+
+    async fn is_running(&self) -> bool {
+        !self.running.lock().await.is_empty()
+    }
+
     async fn update(&self) -> Result<(), Box<dyn std::error::Error>> {
-        if let Some(backup_config) = &self.config.backup {
-            // If keep last was specified, then we have to clean up old backups from the base directory.
-            if let Some(keep_last) = self.backup_config.keep_last {
-                let pool = DB_POOL.get().unwrap();
-                let interval = Utc::now() - Duration::from_secs(keep_last * 24 * 60 * 60);
-                let older_than_interval: Vec<MysqlBackupRow> = sqlx::query_as("SELECT * FROM backups WHERE created_at < $1")
-                    .bind(interval)
-                    .fetch_all(pool)
-                    .await?;
-
-                // Now iterate everything and nuke.
-                for backup in older_than_interval {
-                    let str_path = backup.path.clone();
-                    let path = PathBuf::from_str(&str_path).unwrap();
-                    if path.is_file() {
-                        fs::remove_file(path).await?;
-                    } else {
-                        fs::remove_dir_all(path).await?;
-                    }
-                }
+        self.execute_run(RunTrigger::Cron, None).await
+    }
+
+    async fn shutdown(&self) {
+        for task in self.tasks.lock().await.drain(..) {
+            // Dropping the archiver kills its mysqlbinlog.
+            task.abort();
+            let _ = task.await;
+        }
+    }
+
+    async fn trigger(self: Arc<Self>, job: Option<&str>) -> Result<Option<Uuid>, Box<dyn std::error::Error>> {
+        let service = self.find_job(job)?;
+        if !service.try_set_running().await {
+            return Ok(None);
+        }
+        let run = match service.begin_run(RunTrigger::Manual).await.map_err(|error| error.to_string()) {
+            Ok(run) => run,
+            Err(error) => {
+                service.set_running(false).await;
+                return Err(error.into());
+            }
+        };
+
+        let run_uuid = run.uuid;
+        let previous = service.last_run().await;
+        tokio::spawn(async move {
+            info!("Running backup for MySQL service: {}, run: {}", service.label(), run.uuid);
+            let result = service.complete_run(run, None).await.map_err(|error| error.to_string());
+            match &result {
+                Ok(_) => info!("Backup completed!"),
+                Err(error) => error!("Failed to run backup for MySQL service: {}, error: {}", service.label(), error)
+            }
+            service.notify_run(previous, result.err()).await;
+            service.set_running(false).await;
+        });
+        Ok(Some(run_uuid))
+    }
+}
+
+impl MySQLService {
+    /// Prunes old backups and takes a new one, `xtrabackup_mode` overrides what the configuration asks for.
+    pub async fn run_backup(&self, xtrabackup_mode: Option<XtraBackupMode>, run_uuid: Option<Uuid>) -> Result<(), Box<dyn std::error::Error>> {
+        if let Some(backup_config) = &self.backup {
+            // Clean up whatever the retention policy no longer keeps, a storage which can't be reached must not stop new backups.
+            if let Err(error) = self.prune_backups().await.map_err(|error| error.to_string()) {
+                error!("Failed to prune backups of {}, error: {}", self.label(), error);
+                let host = CATALOG.get().unwrap().host();
+                notify(self.notifications.as_ref(), &Notification::retention_failed(&self.name, self.job.as_deref(), host, error)).await;
             }
 
             // Otherwise we simply do the task.
             match &backup_config.backup_type {
-                MySQLBackupType::XtraBackup(config) => self.do_xtrabackup(config).await?,
-                MySQLBackupType::MySqlDump(config) => self.do_mysqldump(config).await?
+                MySQLBackupType::XtraBackup(config) => {
+                    let mode = xtrabackup_mode.unwrap_or(XtraBackupMode::from_config(config));
+                    self.do_xtrabackup(config, mode, run_uuid).await?
+                }
+                MySQLBackupType::MySqlDump(config) => self.do_mysqldump(config, run_uuid).await?
             }
         }
         Ok(())
     }
+
+    /// Runs a backup and records it in the run history, along with the error and tool output when it fails.
+    pub async fn execute_run(&self, trigger: RunTrigger, xtrabackup_mode: Option<XtraBackupMode>) -> Result<(), Box<dyn std::error::Error>> {
+        let run = self.begin_run(trigger).await?;
+        self.complete_run(run, xtrabackup_mode).await
+    }
+
+    /// Records that a run started, `complete_run` takes the backup.
+    pub async fn begin_run(&self, trigger: RunTrigger) -> Result<RunRow, Box<dyn std::error::Error>> {
+        let catalog = CATALOG.get().unwrap();
+        let run = RunRow::start(&self.name, self.job.as_deref(), trigger, catalog.host());
+        catalog.insert_run(&run).await?;
+        Ok(run)
+    }
+
+    /// Takes the backup of a run `begin_run` recorded, between its hooks, and records how it ended.
+    pub async fn complete_run(&self, mut run: RunRow, xtrabackup_mode: Option<XtraBackupMode>) -> Result<(), Box<dyn std::error::Error>> {
+        let catalog = CATALOG.get().unwrap();
+        let mut failure = self.run_hook(Hook::PreBackup, &run, None).await.err().map(describe_error);
+        if failure.is_none() {
+            failure = self.run_backup(xtrabackup_mode, Some(run.uuid)).await.err().map(describe_error);
+            let post_backup = self.run_hook(Hook::PostBackup, &run, failure.as_ref().map(|(error, _)| error.as_str())).await.err().map(describe_error);
+            match (post_backup, &failure) {
+                (Some(post_backup), None) => failure = Some(post_backup),
+                (Some((error, _)), Some(_)) => error!("Hook post_backup of {} failed as well, error: {}", self.label(), error),
+                _ => {}
+            }
+        }
+
+        // How the run ended is settled by now, so these only get logged when they fail.
+        let hook = if failure.is_some() { Hook::OnFailure } else { Hook::OnSuccess };
+        if let Some((error, _)) = self.run_hook(hook, &run, failure.as_ref().map(|(error, _)| error.as_str())).await.err().map(describe_error) {
+            error!("Hook {} of {} failed, error: {}", hook.as_str(), self.label(), error);
+        }
+
+        let (error, stderr) = match failure {
+            Some((error, stderr)) => (Some(error), stderr),
+            None => (None, None)
+        };
+        run.finish(error.clone(), stderr);
+        catalog.update_run(&run).await?;
+
+        match error {
+            Some(error) => Err(error.into()),
+            None => Ok(())
+        }
+    }
+
+    /// The backups a run took.
+    pub async fn run_backups(&self, catalog: &dyn Catalog, run_uuid: Uuid) -> Result<Vec<MysqlBackupRow>, sqlx::Error> {
+        let backups = catalog.find_backups(Some(&self.name)).await?;
+        Ok(backups.into_iter().filter(|backup| backup.run_uuid == Some(run_uuid)).collect())
+    }
+
+    /// Runs one of the configured hooks, if set, telling it about the run and, once taken, its backups.
+    async fn run_hook(&self, hook: Hook, run: &RunRow, error: Option<&str>) -> Result<(), Box<dyn std::error::Error>> {
+        let Some(backup_config) = &self.backup else { return Ok(()) };
+        let command = match hook {
+            Hook::PreBackup => &backup_config.pre_backup,
+            Hook::PostBackup => &backup_config.post_backup,
+            Hook::OnSuccess => &backup_config.on_success,
+            Hook::OnFailure => &backup_config.on_failure
+        };
+        let Some(command) = command else { return Ok(()) };
+        let limit = match &backup_config.hook_timeout {
+            Some(hook_timeout) => parse_duration(hook_timeout)?,
+            None => DEFAULT_HOOK_TIMEOUT
+        };
+
+        let catalog = CATALOG.get().unwrap();
+        let backups = match hook {
+            Hook::PreBackup => vec![],
+            _ => self.run_backups(catalog.as_ref(), run.uuid).await?
+        };
+        let status = match (hook, error) {
+            (Hook::PreBackup, _) => "running",
+            (_, Some(_)) => "failed",
+            (_, None) => "succeeded"
+        };
+        // A mysqldump run writes one backup per database, so these list one per line.
+        let env = [
+            ("MYSQL_BACKUP_HOOK", hook.as_str().to_string()),
+            ("MYSQL_BACKUP_SERVICE", self.name.clone()),
+            ("MYSQL_BACKUP_JOB", self.job.clone().unwrap_or_default()),
+            ("MYSQL_BACKUP_HOST", catalog.host().to_string()),
+            ("MYSQL_BACKUP_RUN", run.uuid.to_string()),
+            ("MYSQL_BACKUP_STATUS", status.to_string()),
+            ("MYSQL_BACKUP_ERROR", error.unwrap_or_default().to_string()),
+            ("MYSQL_BACKUP_UUID", backups.iter().map(|backup| backup.uuid.to_string()).collect::<Vec<_>>().join("\n")),
+            ("MYSQL_BACKUP_PATH", backups.iter().map(|backup| backup.path.clone()).collect::<Vec<_>>().join("\n")),
+            ("MYSQL_BACKUP_SIZE", backups.iter().map(|backup| backup.size).sum::<i64>().to_string()),
+        ];
+        run_hook(hook, command, &env, limit).await
+    }
+
+    /// The last finished run of this service, used to tell a recovery from a plain success.
+    pub async fn last_run(&self) -> Option<RunRow> {
+        match CATALOG.get().unwrap().find_last_run(&self.name, self.job.as_deref(), None).await {
+            Ok(run) => run,
+            Err(error) => {
+                warn!("Failed to look up the last run of {}, error: {}", self.label(), error);
+                None
+            }
+        }
+    }
+
+    /// Tells the notification targets how the run that just ended went, `previous` is what `last_run` returned before it.
+    pub async fn notify_run(&self, previous: Option<RunRow>, error: Option<String>) {
+        if self.notifications.is_none() {
+            return;
+        }
+        let catalog = CATALOG.get().unwrap();
+        let finished = async {
+            let run = catalog.find_last_run(&self.name, self.job.as_deref(), None).await?
+                .filter(|run| previous.as_ref().is_none_or(|previous| previous.uuid != run.uuid));
+            let backups = match &run {
+                Some(run) => self.run_backups(catalog.as_ref(), run.uuid).await?,
+                None => vec![]
+            };
+            Ok::<_, sqlx::Error>((run, backups))
+        }.await;
+
+        let notification = match (finished, error) {
+            (Ok((Some(run), backups)), _) => Notification::for_run(&run, previous.as_ref(), &backups),
+            // The run never made it into the catalog, so the error is all there is to report.
+            (_, Some(error)) => Notification::failure(&self.name, self.job.as_deref(), catalog.host(), error),
+            (Ok((None, _)), None) => return,
+            (Err(error), None) => {
+                error!("Failed to look up the run of {} to notify about, error: {}", self.label(), error);
+                return;
+            }
+        };
+        notify(self.notifications.as_ref(), &notification).await;
+    }
+
+    /// Creates a job which runs a backup on the given schedule, unless one is already running.
+    fn create_backup_job(service: Arc<MySQLService>, schedule: &str, xtrabackup_mode: Option<XtraBackupMode>) -> Result<Job, Box<dyn std::error::Error>> {
+        let job = Job::new_async(Schedule::from_str(schedule)?, move |uuid, _| {
+            let self_clone = service.clone();
+
+            Box::pin(async move {
+                if !self_clone.try_set_running().await {
+                    warn!("MySQL backup {} already running.", self_clone.label());
+                    return;
+                }
+
+                info!("Running backup for MySQL service: {}, UUID: {}", self_clone.label(), uuid);
+
+                let previous = self_clone.last_run().await;
+                let result = match xtrabackup_mode {
+                    Some(mode) => self_clone.execute_run(RunTrigger::Cron, Some(mode)).await,
+                    None => self_clone.update().await
+                };
+                let result = result.map_err(|error| error.to_string());
+                match &result {
+                    Ok(_) => {
+                        info!("Backup completed!");
+                    }
+                    Err(error) => {
+                        error!("Failed to run backup for MySQL service: {}, error: {}", self_clone.label(), error);
+                    }
+                };
+                self_clone.notify_run(previous, result.err()).await;
+
+                self_clone.set_running(false).await;
+            })
+        })?;
+        Ok(job)
+    }
 }
 
 #[async_trait]
 impl ServiceScheduler for MySQLService {
-    async fn schedule<T: Service + Any>(service: Arc<T>, sched: &mut JobScheduler, service_name: &str) -> Result<(), Box<dyn std::error::Error>> {
+    async fn schedule<T: Service + Any>(service: Arc<T>, sched: &mut JobScheduler, _service_name: &str) -> Result<(), Box<dyn std::error::Error>> {
         let service_clone = service.clone();
-        match Arc::downcast::<MySQLService>(service_clone) {
-            Ok(mysql_service) => {
-                if let Some(backup_config) = &mysql_service.config.backup {
-                    let service_name = service_name.to_string();
-                    let backup_timer = backup_config.interval.clone();
-
-                    let job = Job::new_async(Schedule::from_str(&backup_timer)?, move |uuid, _| {
-                        let service_name = service_name.clone();
-                        let self_clone = mysql_service.clone();
-
-                        Box::pin(async move {
-                            if !self_clone.try_set_running().await {
-                                warn!("MySQL backup already running.");
-                                return;
-                            }
-
-                            info!("Running backup for MySQL service: {}, UUID: {}", service_name, uuid);
-
-                            match self_clone.update().await {
-                                Ok(_) => {
-                                    info!("Backup completed!");
-                                }
-                                Err(error) => {
-                                    error!("Failed to run backup for MySQL service: {}, error: {}", service_name, error);
-                                }
-                            };
-
-                            self_clone.set_running(false).await;
-                        })
-                    })?;
-
-                    sched.add(job).await?;
+        if let Ok(mysql_service) = Arc::downcast::<MySQLService>(service_clone) {
+            // Every backup job runs on its own schedule, and only waits for itself.
+            for job_service in mysql_service.job_services() {
+                let job_service = Arc::new(job_service);
+                let Some(backup_config) = &job_service.backup else { continue };
+                match &backup_config.backup_type {
+                    // With an incremental schedule, the regular interval takes the full backups.
+                    MySQLBackupType::XtraBackup(XtraBackupConfig { incremental_interval: Some(incremental_interval), .. }) => {
+                        let full_job = MySQLService::create_backup_job(job_service.clone(), &backup_config.interval, Some(XtraBackupMode::Full))?;
+                        sched.add(full_job).await?;
+                        let incremental_job = MySQLService::create_backup_job(job_service.clone(), incremental_interval, Some(XtraBackupMode::Incremental))?;
+                        sched.add(incremental_job).await?;
+                    }
+                    _ => {
+                        let job = MySQLService::create_backup_job(job_service.clone(), &backup_config.interval, None)?;
+                        sched.add(job).await?;
+                    }
                 }
             }
-            Err(_) => {}
+
+            // Binary logs are archived continuously and recorded in the catalog on their own schedule.
+            if let Some(binlog_config) = mysql_service.config.binlog.clone() {
+                let archiver_service = mysql_service.clone();
+                let archiver_config = binlog_config.clone();
+                let archiver = tokio::spawn(async move {
+                    loop {
+                        match archiver_service.archive_binlogs(&archiver_config).await {
+                            Ok(_) => warn!("Binary log archiver for {} stopped, restarting.", archiver_service.name),
+                            Err(error) => error!("Binary log archiver for {} failed, error: {}", archiver_service.name, error)
+                        }
+                        sleep(Duration::from_secs(30)).await;
+                    }
+                });
+                mysql_service.tasks.lock().await.push(archiver);
+
+                let index_service = mysql_service.clone();
+                let job = Job::new_async(Schedule::from_str(&binlog_config.index_interval)?, move |_, _| {
+                    let self_clone = index_service.clone();
+                    let binlog_config = binlog_config.clone();
+
+                    Box::pin(async move {
+                        if let Err(error) = self_clone.index_binlogs(&binlog_config).await {
+                            error!("Failed to index binary logs for MySQL service: {}, error: {}", self_clone.name, error);
+                        }
+                    })
+                })?;
+
+                sched.add(job).await?;
+            }
         }
         Ok(())
     }
@@ -1,8 +1,7 @@
 use std::path::{Path, PathBuf};
-use std::process::Stdio;
 use std::str::FromStr;
 use async_trait::async_trait;
-use log::debug;
+use log::{debug, error};
 use sqlx::mysql::MySqlConnectOptions;
 use sqlx::{MySqlPool, Row};
 use sqlx::types::chrono::{Local, Utc};
@@ -10,40 +9,88 @@ use tokio::fs;
 use tokio::process::Command;
 use uuid::{NoContext, Timestamp, Uuid};
 use which::which;
-use crate::DB_POOL;
+use crate::CATALOG;
+use crate::artifact::{artifact_extension, remove_artifact, run_to_artifact, ArtifactOutput};
+use crate::encryption::join_recipients;
+use crate::service::mysql::binlog::{parse_dump_header, BinlogCoordinates};
 use crate::service::mysql::config::MySQLDumpConfig;
+use crate::service::mysql::database::MysqlBackupRow;
 use crate::service::mysql::mysql_defaults::MySqlDefaultsReader;
 use crate::service::mysql::mysql_service::MySQLService;
-use crate::utils::get_size;
+use crate::utils::{get_size, ToolError};
 
-pub fn create_command(defaults_path: &Path, file_path: PathBuf) -> Result<Command, Box<dyn std::error::Error>> {
+pub fn create_command(defaults_path: &Path, source_data_option: Option<&str>) -> Result<Command, Box<dyn std::error::Error>> {
     let command_path = which("mysqldump")?;
     let mut cmd = Command::new(command_path);
     cmd.arg(format!("--defaults-file={}", defaults_path.to_str().unwrap()));
     cmd.arg("--quick");
     cmd.arg("--single-transaction");
-    cmd.arg(format!("--result-file={}", file_path.to_str().unwrap()));
+
+    // Record the binary log coordinates as a comment in the dump header.
+    if let Some(source_data_option) = source_data_option {
+        cmd.arg(format!("{}=2", source_data_option));
+    }
     Ok(cmd)
 }
 
+/// Figures out how to ask mysqldump for the binary log coordinates, `--master-data` was renamed in 8.0.26.
+async fn source_data_option() -> Result<&'static str, Box<dyn std::error::Error>> {
+    let command_path = which("mysqldump")?;
+    let output = Command::new(command_path).arg("--help").output().await?;
+    if String::from_utf8_lossy(&output.stdout).contains("--source-data") {
+        Ok("--source-data")
+    } else {
+        Ok("--master-data")
+    }
+}
+
+/// Reads the binary log coordinates from the start of a dump, the file itself may already be encrypted.
+fn read_dump_coordinates(head: &[u8]) -> Option<BinlogCoordinates> {
+    let head = String::from_utf8_lossy(head);
+    // The coordinates are always written before the first table.
+    let header = match head.find("-- Table structure") {
+        Some(end) => &head[..end],
+        None => &head
+    };
+    parse_dump_header(header)
+}
+
+/// Checks that mysqldump exited cleanly and got to the end, returning why not otherwise.
+fn check_dump(output: &ArtifactOutput) -> Result<(), String> {
+    if !output.status.success() {
+        return Err(format!("mysqldump exited with {}", output.status));
+    }
+    // mysqldump writes this trailer last, without it the dump was cut short.
+    if !String::from_utf8_lossy(&output.tail).contains("-- Dump completed") {
+        return Err("the dump is missing its \"-- Dump completed\" trailer".to_string());
+    }
+    Ok(())
+}
+
 #[async_trait]
 pub trait MySqlDumpRunner {
-    async fn do_mysqldump(&self, mysql_config: &MySQLDumpConfig) -> Result<(), Box<dyn std::error::Error>>;
+    async fn do_mysqldump(&self, mysql_config: &MySQLDumpConfig, run_uuid: Option<Uuid>) -> Result<(), Box<dyn std::error::Error>>;
 
-    async fn save_backup(&self, path: PathBuf) -> Result<(), Box<dyn std::error::Error>>;
+    /// Records a finished dump in the catalog, returning its uuid.
+    async fn save_backup(&self, path: PathBuf, database: &str, coordinates: Option<BinlogCoordinates>, checksum: &str, run_uuid: Option<Uuid>) -> Result<Uuid, Box<dyn std::error::Error>>;
 }
 
 #[async_trait]
 impl MySqlDumpRunner for MySQLService {
-    async fn do_mysqldump(&self, mysql_config: &MySQLDumpConfig) -> Result<(), Box<dyn std::error::Error>> {
-        if let Some(config) = &self.config.backup {
+    async fn do_mysqldump(&self, mysql_config: &MySQLDumpConfig, run_uuid: Option<Uuid>) -> Result<(), Box<dyn std::error::Error>> {
+        if let Some(config) = &self.backup {
             let defaults = self.get_defaults_file().await?;
             let defaults_path = defaults.path();
 
             // Create new pool.
             let connection_config = MySqlConnectOptions::from_defaults_file(defaults_path)?;
             let pool = MySqlPool::connect_lazy_with(connection_config);
+            // Named jobs go into the file names, so two jobs dumping the same database never collide.
             let current_date = Local::now().format("%Y-%m-%d_%H-%M-%S").to_string();
+            let current_date = match &self.job {
+                Some(job) => format!("{}_{}", current_date, job),
+                None => current_date
+            };
 
             // Fetch the list of databases.
             let databases = if let Some(databases) = &config.databases {
@@ -51,7 +98,7 @@ impl MySqlDumpRunner for MySQLService {
             } else {
                 // If databases are not provided, fetch all databases except the excluded ones
                 let excluded_databases = config.databases_exclude.clone().unwrap_or_default();
-                let excluded_default_databases = vec!["information_schema".to_string(), "mysql".to_string(), "performance_schema".to_string(), "sys".to_string()];
+                let excluded_default_databases = ["information_schema".to_string(), "mysql".to_string(), "performance_schema".to_string(), "sys".to_string()];
                 let databases = sqlx::query("SHOW DATABASES")
                     .fetch_all(&pool)
                     .await?
@@ -62,7 +109,22 @@ impl MySqlDumpRunner for MySQLService {
                 databases
             };
 
-            // Iterate each database and dump it individually.
+            // Only ask for binary log coordinates when binary logging is enabled, mysqldump fails otherwise.
+            let log_bin: i64 = sqlx::query("SELECT @@log_bin").fetch_one(&pool).await?.get(0);
+            let source_data_option = if log_bin == 1 {
+                Some(source_data_option().await?)
+            } else {
+                None
+            };
+
+            // Compressed and encrypted dumps get their extensions on top of `.sql`.
+            let compression = config.compression.as_ref();
+            let recipients = self.encryption_recipients()?;
+            let extension = artifact_extension("sql", compression, !recipients.is_empty());
+
+            // Iterate each database and dump it individually, a failed upload only fails the run once every database is dumped.
+            let catalog = CATALOG.get().unwrap();
+            let mut failures: Vec<ToolError> = vec![];
             for database in &databases {
                 if mysql_config.separate_tables.is_some() && mysql_config.separate_tables.unwrap() {
                     // Fetch the table names for the database
@@ -76,22 +138,31 @@ impl MySqlDumpRunner for MySQLService {
                         debug!("Dumping table: {}.{}", database, table_name);
 
                         // Create a result path, where the SQL will be dumped off to.
-                        let result_path = temp_dir.clone().join(format!("{}.{}.sql", database, table_name));
+                        let result_path = temp_dir.clone().join(format!("{}.{}.{}", database, table_name, extension));
 
                         // Create the command to dump the data.
-                        let mut cmd = create_command(defaults_path, result_path.clone())?;
+                        let mut cmd = create_command(defaults_path, source_data_option)?;
                         cmd.arg(database);
-                        cmd.arg(table_name);
+                        cmd.arg(&table_name);
 
                         // Run the command and expect output.
-                        let status = cmd.stdout(Stdio::null()).status().await?;
-                        if status.success() {
-                            debug!("-> Dumped!");
-                            // Save it to database.
-                            self.save_backup(result_path.clone()).await?;
-
-                        } else {
-                            debug!("-> Failed to dump!");
+                        let output = run_to_artifact(&mut cmd, &result_path, compression, &recipients).await?;
+                        match check_dump(&output) {
+                            Ok(_) => {
+                                debug!("-> Dumped!");
+                                // Save it to database.
+                                let coordinates = read_dump_coordinates(&output.head);
+                                let uuid = self.save_backup(result_path.clone(), database, coordinates, &output.checksum, run_uuid).await?;
+                                if let Err(error) = self.store_backup(catalog.as_ref(), uuid, &result_path).await {
+                                    error!("-> Failed to upload {}.{}: {}", database, table_name, error);
+                                    failures.push(ToolError::new(format!("{}.{}: upload failed: {}", database, table_name, error), String::new()));
+                                }
+                            }
+                            Err(reason) => {
+                                error!("-> Failed to dump {}.{}: {}", database, table_name, reason);
+                                remove_artifact(&result_path).await?;
+                                failures.push(ToolError::new(format!("{}.{}: {}", database, table_name, reason), output.stderr));
+                            }
                         }
                     }
                 } else {
@@ -99,42 +170,104 @@ impl MySqlDumpRunner for MySQLService {
 
                     // Create a result path, where the SQL will be dumped off to.
                     fs::create_dir_all(self.backup_config.basedir.clone()).await?;
-                    let result_path = PathBuf::from_str(&self.backup_config.basedir)?.join(format!("{}-{}.sql", current_date, database));
+                    let result_path = PathBuf::from_str(&self.backup_config.basedir)?.join(format!("{}-{}.{}", current_date, database, extension));
 
                     // Create the command to dump the data.
-                    let mut cmd = create_command(defaults_path, result_path.clone())?;
+                    let mut cmd = create_command(defaults_path, source_data_option)?;
                     cmd.arg(database);
 
                     // Run the command and expect output.
-                    let status = cmd.stdout(Stdio::null()).status().await?;
-                    if status.success() {
-                        debug!("-> Dumped!");
-
-                        // Save it to database.
-                        self.save_backup(result_path.clone()).await?;
-                    } else {
-                        debug!("-> Failed to dump!");
+                    let output = run_to_artifact(&mut cmd, &result_path, compression, &recipients).await?;
+                    match check_dump(&output) {
+                        Ok(_) => {
+                            debug!("-> Dumped!");
+
+                            // Save it to database.
+                            let coordinates = read_dump_coordinates(&output.head);
+                            let uuid = self.save_backup(result_path.clone(), database, coordinates, &output.checksum, run_uuid).await?;
+                            if let Err(error) = self.store_backup(catalog.as_ref(), uuid, &result_path).await {
+                                error!("-> Failed to upload {}: {}", database, error);
+                                failures.push(ToolError::new(format!("{}: upload failed: {}", database, error), String::new()));
+                            }
+                        }
+                        Err(reason) => {
+                            error!("-> Failed to dump {}: {}", database, reason);
+                            remove_artifact(&result_path).await?;
+                            failures.push(ToolError::new(format!("{}: {}", database, reason), output.stderr));
+                        }
                     }
                 }
             }
+
+            // The other databases are still dumped, but the run as a whole failed.
+            if !failures.is_empty() {
+                let message = format!("mysqldump failed for {}", failures.iter().map(|failure| failure.message.clone()).collect::<Vec<String>>().join("; "));
+                let stderr = failures.into_iter().map(|failure| failure.stderr).collect::<Vec<String>>().join("\n");
+                return Err(ToolError::new(message, stderr).into());
+            }
         }
         Ok(())
     }
 
-    async fn save_backup(&self, path: PathBuf) -> Result<(), Box<dyn std::error::Error>> {
+    async fn save_backup(&self, path: PathBuf, database: &str, coordinates: Option<BinlogCoordinates>, checksum: &str, run_uuid: Option<Uuid>) -> Result<Uuid, Box<dyn std::error::Error>> {
         let uuid = Uuid::new_v7(Timestamp::now(NoContext));
         let path_str = path.to_str().unwrap().to_string();
-        let size = get_size(path).unwrap() as i64;
+        let size = get_size(path.clone()).unwrap() as i64;
         let created_at = Utc::now().naive_utc();
+        let compression = self.backup.as_ref().and_then(|backup| backup.compression.as_ref());
+        let recipients = self.encryption_recipients()?;
+        let catalog = CATALOG.get().unwrap();
 
-        sqlx::query("INSERT INTO backups (uuid, type, path, size, created_at) VALUES ($1, 0, $2, $3, $4)")
-            .bind(uuid)
-            .bind(path_str)
-            .bind(size)
-            .bind(created_at)
-            .execute(DB_POOL.get().unwrap())
-            .await?;
+        catalog.insert_backup(&MysqlBackupRow {
+            uuid,
+            base_uuid: None,
+            backup_type: 0,
+            path: path_str,
+            size,
+            created_at,
+            database_name: Some(database.to_string()),
+            binlog_file: coordinates.as_ref().map(|coordinates| coordinates.file.clone()),
+            binlog_position: coordinates.as_ref().map(|coordinates| coordinates.position),
+            gtid_executed: coordinates.and_then(|coordinates| coordinates.gtid_executed),
+            service: Some(self.name.clone()),
+            checkpoint_type: None,
+            from_lsn: None,
+            to_lsn: None,
+            last_lsn: None,
+            compression: compression.map(|compression| compression.codec.name().to_string()),
+            encryption_recipients: join_recipients(&recipients),
+            remote_key: None,
+            checksum: Some(checksum.to_string()),
+            run_uuid,
+            host: Some(catalog.host().to_string()),
+            job: self.job.clone()
+        }).await?;
 
-        Ok(())
+        Ok(uuid)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::process::ExitStatusExt;
+    use std::process::ExitStatus;
+
+    fn output(code: i32, tail: &str) -> ArtifactOutput {
+        ArtifactOutput {
+            status: ExitStatus::from_raw(code << 8),
+            head: vec![],
+            tail: tail.as_bytes().to_vec(),
+            stderr: String::new(),
+            checksum: String::new()
+        }
+    }
+
+    #[test]
+    fn test_check_dump() {
+        assert!(check_dump(&output(0, "UNLOCK TABLES;\n-- Dump completed on 2024-04-16 18:30:00\n")).is_ok());
+        assert!(check_dump(&output(2, "-- Dump completed on 2024-04-16 18:30:00\n")).is_err());
+        // Killed half way, the exit status alone would not tell.
+        assert!(check_dump(&output(0, "INSERT INTO `users` VALUES (1,")).is_err());
     }
 }
@@ -0,0 +1,371 @@
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use async_trait::async_trait;
+use log::{debug, info, warn};
+use sqlx::types::chrono::NaiveDateTime;
+use sqlx::mysql::MySqlConnectOptions;
+use sqlx::MySqlPool;
+use tokio::fs;
+use tokio::process::Command;
+use which::which;
+use crate::catalog::Catalog;
+use crate::service::mysql::database::MysqlBackupRow;
+use crate::service::mysql::mysql_defaults::MySqlDefaultsReader;
+use crate::service::mysql::mysql_service::MySQLService;
+use crate::service::mysql::xtrabackup::parse_xtrabackup_checkpoints;
+use age::x25519::Identity;
+use crate::artifact::open_artifact;
+use crate::config::CompressionCodec;
+use crate::encryption::EXTENSION;
+use crate::utils::copy_dir;
+
+pub struct RestoreOptions {
+    /// Restore into this database instead of the one the dump was taken from.
+    pub target_database: Option<String>,
+    /// When the backup is a single table of a `separate_tables` dump, restore every table next to it.
+    pub all_tables: bool,
+    /// Directory where an xtrabackup chain is copied to and prepared, or an uploaded dump is downloaded to, defaults to `<basedir>/restore-<uuid>`.
+    /// Unlike the default, it is not removed after restoring a dump.
+    pub staging_dir: Option<PathBuf>,
+    /// When set, the prepared xtrabackup is copied back into this datadir.
+    pub copy_back: Option<PathBuf>,
+    /// Roll forward using archived binary logs up to this point in time (UTC).
+    pub until: Option<NaiveDateTime>,
+    /// Roll forward using archived binary logs up to and including this GTID.
+    pub until_gtid: Option<String>,
+    /// age identity file used to decrypt the backup, defaults to the configured `identity_file`.
+    pub identity_file: Option<PathBuf>,
+}
+
+/// Runs xtrabackup with the given arguments and fails if it does not exit successfully.
+async fn run_xtrabackup(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    let command_path = which("xtrabackup")?;
+    debug!("Running xtrabackup {}", args.join(" "));
+
+    let status = Command::new(command_path)
+        .args(args)
+        .stdout(Stdio::null())
+        .status()
+        .await?;
+    if !status.success() {
+        return Err(format!("xtrabackup {} exited with {}.", args.join(" "), status).into());
+    }
+    Ok(())
+}
+
+/// Whether the file is a dump, compressed and encrypted or not.
+fn is_sql_file(path: &Path) -> bool {
+    path.file_name()
+        .and_then(|file_name| file_name.to_str())
+        .map(|file_name| file_name.strip_suffix(&format!(".{}", EXTENSION)).unwrap_or(file_name))
+        .is_some_and(|file_name| CompressionCodec::strip_extension(file_name).ends_with(".sql"))
+}
+
+/// Loads the identities needed for the backup, failing early when it is encrypted and there is no key for it.
+async fn backup_identities(service: &MySQLService, backup: &MysqlBackupRow, options: &RestoreOptions) -> Result<Vec<Identity>, Box<dyn std::error::Error>> {
+    let identities = service.encryption_identities(options.identity_file.as_deref()).await?;
+    if let Some(recipients) = &backup.encryption_recipients {
+        if identities.is_empty() {
+            return Err(format!("Backup {} is encrypted to {}, an identity file is needed to restore it.", backup.uuid, recipients).into());
+        }
+    }
+    Ok(identities)
+}
+
+/// Finds the `backup.xbstream*` file of a streamed xtrabackup, plain backups do not have one.
+async fn find_xbstream(directory: &Path) -> Result<Option<PathBuf>, Box<dyn std::error::Error>> {
+    let mut entries = fs::read_dir(directory).await?;
+    while let Some(entry) = entries.next_entry().await? {
+        if entry.file_name().to_str().is_some_and(|file_name| file_name.starts_with("backup.xbstream")) {
+            return Ok(Some(entry.path()));
+        }
+    }
+    Ok(None)
+}
+
+/// Unpacks a streamed backup into `target` by piping it through `xbstream -x`.
+async fn extract_xbstream(stream_path: &Path, target: &Path, identities: &[Identity]) -> Result<(), Box<dyn std::error::Error>> {
+    let command_path = which("xbstream")?;
+    fs::create_dir_all(target).await?;
+
+    let mut child = Command::new(command_path)
+        .arg("-x")
+        .arg("-C")
+        .arg(target)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .spawn()?;
+    let mut stdin = child.stdin.take().unwrap();
+    tokio::io::copy(&mut open_artifact(stream_path, identities).await?, &mut stdin).await?;
+    drop(stdin);
+
+    let status = child.wait().await?;
+    if !status.success() {
+        return Err(format!("Failed to extract {}, xbstream exited with {}.", stream_path.to_str().unwrap(), status).into());
+    }
+    Ok(())
+}
+
+/// Fills in the LSNs of a backup recorded before the catalog tracked them from the `xtrabackup_checkpoints` of its copy.
+async fn fill_lsns(backup: &mut MysqlBackupRow, directory: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    if backup.from_lsn.is_some() && backup.to_lsn.is_some() {
+        return Ok(());
+    }
+    let checkpoints_path = directory.join("xtrabackup_checkpoints");
+    let checkpoints = if checkpoints_path.is_file() {
+        parse_xtrabackup_checkpoints(&fs::read_to_string(checkpoints_path).await?)
+    } else {
+        None
+    };
+    match checkpoints {
+        Some(checkpoints) => {
+            backup.from_lsn.get_or_insert(checkpoints.from_lsn);
+            backup.to_lsn.get_or_insert(checkpoints.to_lsn);
+        }
+        None => warn!("Backup {} has no LSNs recorded or in {}, its place in the chain can't be checked.", backup.uuid, directory.to_str().unwrap())
+    }
+    Ok(())
+}
+
+/// Collects the SQL files which have to be replayed for the given backup path.
+async fn collect_sql_files(path: &Path, all_tables: bool) -> Result<Vec<PathBuf>, Box<dyn std::error::Error>> {
+    let directory = if path.is_dir() {
+        path.to_path_buf()
+    } else if all_tables {
+        match path.parent() {
+            Some(parent) => parent.to_path_buf(),
+            None => return Ok(vec![path.to_path_buf()])
+        }
+    } else {
+        return Ok(vec![path.to_path_buf()])
+    };
+
+    let mut files = vec![];
+    let mut entries = fs::read_dir(&directory).await?;
+    while let Some(entry) = entries.next_entry().await? {
+        let entry_path = entry.path();
+        if entry_path.is_file() && is_sql_file(&entry_path) {
+            files.push(entry_path);
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+#[async_trait]
+pub trait MySqlRestoreRunner {
+    async fn restore_mysqldump(&self, backup: &MysqlBackupRow, options: &RestoreOptions) -> Result<(), Box<dyn std::error::Error>>;
+
+    async fn restore_xtrabackup(&self, catalog: &dyn Catalog, backup: &MysqlBackupRow, options: &RestoreOptions) -> Result<PathBuf, Box<dyn std::error::Error>>;
+
+    /// Replays the SQL files of a dump into `database`, fetching the uploaded copy into `download_dir` when needed.
+    async fn restore_dump_files(&self, backup: &MysqlBackupRow, options: &RestoreOptions, database: &str, download_dir: &Path) -> Result<(), Box<dyn std::error::Error>>;
+}
+
+#[async_trait]
+impl MySqlRestoreRunner for MySQLService {
+    async fn restore_mysqldump(&self, backup: &MysqlBackupRow, options: &RestoreOptions) -> Result<(), Box<dyn std::error::Error>> {
+        if backup.backup_type != 0 {
+            return Err(format!("Backup {} is not a mysqldump backup.", backup.uuid).into());
+        }
+
+        // Figure out which database we are restoring into.
+        let database = match options.target_database.as_ref().or(backup.database_name.as_ref()) {
+            Some(database) => database.clone(),
+            None => return Err(format!("Backup {} has no database recorded, a target database must be given.", backup.uuid).into())
+        };
+
+        // Only the uploaded copy may be left, in which case it is downloaded next to the backups or into the staging directory.
+        let download_dir = match &options.staging_dir {
+            Some(staging_dir) => staging_dir.clone(),
+            None => PathBuf::from(&self.backup_config.basedir).join(format!("restore-{}", backup.uuid))
+        };
+        let result = self.restore_dump_files(backup, options, &database, &download_dir).await.map_err(|error| error.to_string());
+
+        // The downloaded copy is only needed while restoring, a staging directory the operator asked for is theirs to clean up.
+        if options.staging_dir.is_none() && download_dir.exists() {
+            fs::remove_dir_all(&download_dir).await?;
+        }
+        result.map_err(|error| error.into())
+    }
+
+    async fn restore_dump_files(&self, backup: &MysqlBackupRow, options: &RestoreOptions, database: &str, download_dir: &Path) -> Result<(), Box<dyn std::error::Error>> {
+        let path = self.fetch_backup(backup, download_dir).await?;
+        info!("Restoring backup {} ({} bytes, {}) taken at {}", backup.uuid, backup.size, backup.compression.as_deref().unwrap_or("uncompressed"), backup.created_at);
+        let files = collect_sql_files(&path, options.all_tables).await?;
+        if files.is_empty() {
+            return Err(format!("No SQL files found for backup {}.", backup.uuid).into());
+        }
+
+        let defaults = self.get_defaults_file().await?;
+        let defaults_path = defaults.path();
+
+        // Make sure the target database exists, mysqldump does not emit CREATE DATABASE for single databases.
+        let connection_config = MySqlConnectOptions::from_defaults_file(defaults_path)?;
+        let pool = MySqlPool::connect_lazy_with(connection_config);
+        sqlx::query(&format!("CREATE DATABASE IF NOT EXISTS `{}`", database.replace('`', "``")))
+            .execute(&pool)
+            .await?;
+        pool.close().await;
+
+        let identities = backup_identities(self, backup, options).await?;
+        let command_path = which("mysql")?;
+        for file in files {
+            info!("Restoring {} into database {}", file.to_str().unwrap(), database);
+
+            let mut cmd = Command::new(&command_path);
+            cmd.arg(format!("--defaults-file={}", defaults_path.to_str().unwrap()));
+            cmd.arg(database);
+            cmd.stdin(Stdio::piped());
+
+            // Feed the dump, decrypting and decompressing it on the way in.
+            let mut child = cmd.stdout(Stdio::null()).spawn()?;
+            let mut stdin = child.stdin.take().unwrap();
+            tokio::io::copy(&mut open_artifact(&file, &identities).await?, &mut stdin).await?;
+            drop(stdin);
+            let status = child.wait().await?;
+            if status.success() {
+                debug!("-> Restored!");
+            } else {
+                return Err(format!("Failed to restore {}, mysql exited with {}.", file.to_str().unwrap(), status).into());
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn restore_xtrabackup(&self, catalog: &dyn Catalog, backup: &MysqlBackupRow, options: &RestoreOptions) -> Result<PathBuf, Box<dyn std::error::Error>> {
+        if backup.backup_type != 1 {
+            return Err(format!("Backup {} is not an xtrabackup backup.", backup.uuid).into());
+        }
+
+        // Walk the chain back to the full backup.
+        let mut chain = MysqlBackupRow::find_chain(catalog, backup.uuid).await?;
+        MysqlBackupRow::validate_chain(&chain)?;
+        info!("Restoring backup {} using a chain of {} backup(s), up to LSN {}.", backup.uuid, chain.len(), backup.last_lsn.map_or("unknown".to_string(), |lsn| lsn.to_string()));
+        for member in &chain {
+            if !PathBuf::from(&member.path).is_dir() && member.remote_key.is_none() {
+                return Err(format!("Backup directory {} of {} does not exist.", member.path, member.uuid).into());
+            }
+        }
+
+        // The staging directory must be fresh, otherwise we would prepare on top of an older attempt.
+        let staging_dir = match &options.staging_dir {
+            Some(staging_dir) => staging_dir.clone(),
+            None => PathBuf::from(&self.backup_config.basedir).join(format!("restore-{}", backup.uuid))
+        };
+        if staging_dir.exists() {
+            return Err(format!("Staging directory {} already exists.", staging_dir.to_str().unwrap()).into());
+        }
+
+        // Copy the chain, the full backup becomes the target everything is applied onto.
+        let identities = backup_identities(self, backup, options).await?;
+        let base_dir = staging_dir.join("base");
+        let mut incremental_dirs = vec![];
+        for (index, member) in chain.iter_mut().enumerate() {
+            let target = if index == 0 {
+                base_dir.clone()
+            } else {
+                let incremental_dir = staging_dir.join(format!("inc-{}", index));
+                incremental_dirs.push(incremental_dir.clone());
+                incremental_dir
+            };
+            debug!("Copying {} to {}", member.path, target.to_str().unwrap());
+
+            let source = self.fetch_backup(member, &staging_dir.join("download")).await?;
+            let stream_path = find_xbstream(&source).await?;
+            match stream_path {
+                Some(stream_path) => extract_xbstream(&stream_path, &target, &identities).await?,
+                None => {
+                    let copy_target = target.clone();
+                    tokio::task::spawn_blocking(move || copy_dir(source, copy_target)).await??
+                }
+            }
+            fill_lsns(member, &target).await?;
+        }
+
+        // Older backups only have their LSNs in the copies, so the chain is checked again now that those are known.
+        MysqlBackupRow::validate_chain(&chain)?;
+
+        // Prepare the full backup, and apply each incremental in order while keeping the redo log open.
+        let target_dir = format!("--target-dir={}", base_dir.to_str().unwrap());
+        if !incremental_dirs.is_empty() {
+            run_xtrabackup(&["--prepare".to_string(), "--apply-log-only".to_string(), target_dir.clone()]).await?;
+            for incremental_dir in &incremental_dirs {
+                info!("Applying incremental {}", incremental_dir.to_str().unwrap());
+                run_xtrabackup(&[
+                    "--prepare".to_string(),
+                    "--apply-log-only".to_string(),
+                    target_dir.clone(),
+                    format!("--incremental-dir={}", incremental_dir.to_str().unwrap()),
+                ]).await?;
+            }
+        }
+
+        // Final prepare, rolls back uncommitted transactions and makes the datadir usable.
+        run_xtrabackup(&["--prepare".to_string(), target_dir.clone()]).await?;
+        info!("Prepared backup is available in {}", base_dir.to_str().unwrap());
+
+        if let Some(datadir) = &options.copy_back {
+            info!("Copying back into {}", datadir.to_str().unwrap());
+            run_xtrabackup(&[
+                "--copy-back".to_string(),
+                target_dir.clone(),
+                format!("--datadir={}", datadir.to_str().unwrap()),
+            ]).await?;
+        }
+
+        Ok(base_dir)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sqlx::types::Uuid;
+    use tempfile::tempdir;
+
+    #[tokio::test]
+    async fn test_fill_lsns() {
+        let dir = tempdir().unwrap();
+        std::fs::write(dir.path().join("xtrabackup_checkpoints"), "backup_type = incremental\nfrom_lsn = 100\nto_lsn = 200\nlast_lsn = 210\n").unwrap();
+        let mut backup = MysqlBackupRow {
+            base_uuid: Some(Uuid::from_u128(1)),
+            checkpoint_type: Some("incremental".to_string()),
+            ..MysqlBackupRow::test_default(2)
+        };
+
+        // A backup recorded without LSNs picks them up from its copy.
+        fill_lsns(&mut backup, dir.path()).await.unwrap();
+        assert_eq!((backup.from_lsn, backup.to_lsn), (Some(100), Some(200)));
+
+        // Recorded LSNs win, and a copy without checkpoints leaves the backup alone.
+        backup.to_lsn = Some(300);
+        fill_lsns(&mut backup, dir.path()).await.unwrap();
+        assert_eq!(backup.to_lsn, Some(300));
+        let mut legacy = MysqlBackupRow { from_lsn: None, to_lsn: None, ..backup };
+        fill_lsns(&mut legacy, &dir.path().join("missing")).await.unwrap();
+        assert_eq!((legacy.from_lsn, legacy.to_lsn), (None, None));
+    }
+
+    #[tokio::test]
+    async fn test_collect_sql_files() {
+        let dir = tempdir().unwrap();
+        for name in ["auth.users.sql", "auth.groups.sql.zst", "auth.roles.sql.gz.age", "notes.txt"] {
+            std::fs::write(dir.path().join(name), "").unwrap();
+        }
+        let table_path = dir.path().join("auth.users.sql");
+
+        // A single table only restores itself.
+        let files = collect_sql_files(&table_path, false).await.unwrap();
+        assert_eq!(files, vec![table_path.clone()]);
+
+        // Restoring all tables picks up every sibling SQL file in order.
+        let files = collect_sql_files(&table_path, true).await.unwrap();
+        assert_eq!(files, vec![dir.path().join("auth.groups.sql.zst"), dir.path().join("auth.roles.sql.gz.age"), table_path.clone()]);
+
+        // Directories are always expanded.
+        let files = collect_sql_files(dir.path(), false).await.unwrap();
+        assert_eq!(files.len(), 3);
+    }
+}
@@ -0,0 +1,58 @@
+use std::path::PathBuf;
+use async_trait::async_trait;
+use log::debug;
+use tempfile::TempDir;
+use tokio::fs;
+use crate::checksum::{sha256_file, verify_manifest, MANIFEST_FILE};
+use crate::service::mysql::database::MysqlBackupRow;
+use crate::service::mysql::mysql_service::MySQLService;
+
+#[async_trait]
+pub trait MySqlVerifyRunner {
+    /// Re-hashes the backup, returning a description of every mismatch with the catalog.
+    async fn verify_backup(&self, backup: &MysqlBackupRow, remote: bool) -> Result<Vec<String>, Box<dyn std::error::Error>>;
+}
+
+#[async_trait]
+impl MySqlVerifyRunner for MySQLService {
+    async fn verify_backup(&self, backup: &MysqlBackupRow, remote: bool) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+        let Some(expected) = &backup.checksum else {
+            return Err(format!("Backup {} has no checksum recorded.", backup.uuid).into());
+        };
+
+        // Downloads go next to the backups and are removed again once verified.
+        fs::create_dir_all(&self.backup_config.basedir).await?;
+        let download_dir = TempDir::with_prefix_in(format!("verify-{}", backup.uuid), &self.backup_config.basedir)?;
+        let path: PathBuf = if remote {
+            self.download_backup(backup, download_dir.path()).await?
+        } else {
+            self.fetch_backup(backup, download_dir.path()).await?
+        };
+        debug!("Verifying backup {} at {}", backup.uuid, path.to_str().unwrap());
+
+        let mut mismatches = vec![];
+        if path.is_dir() {
+            // The catalog holds the checksum of the manifest, which in turn holds the checksum of every file.
+            let manifest_path = path.join(MANIFEST_FILE);
+            if !manifest_path.is_file() {
+                mismatches.push(format!("{} is missing", MANIFEST_FILE));
+                return Ok(mismatches);
+            }
+            let actual = sha256_file(&manifest_path).await?;
+            if actual != *expected {
+                mismatches.push(format!("{} has checksum {}, expected {}", MANIFEST_FILE, actual, expected));
+            }
+            mismatches.extend(verify_manifest(&path).await?);
+        } else {
+            let size = fs::metadata(&path).await?.len() as i64;
+            if size != backup.size {
+                mismatches.push(format!("{} is {} bytes, expected {}", path.to_str().unwrap(), size, backup.size));
+            }
+            let actual = sha256_file(&path).await?;
+            if actual != *expected {
+                mismatches.push(format!("{} has checksum {}, expected {}", path.to_str().unwrap(), actual, expected));
+            }
+        }
+        Ok(mismatches)
+    }
+}
@@ -1,27 +1,89 @@
 use std::path::PathBuf;
-use std::process::Stdio;
+use std::process::{ExitStatus, Stdio};
 use async_trait::async_trait;
-use log::debug;
+use log::{debug, error, info, warn};
 use sqlx::types::chrono::{Local, Utc};
 use tokio::fs;
 use tokio::process::Command;
 use uuid::{NoContext, Timestamp, Uuid};
 use which::which;
-use crate::DB_POOL;
+use crate::CATALOG;
+use crate::artifact::{artifact_extension, read_stderr, run_to_artifact};
+use crate::checksum::write_manifest;
+use crate::encryption::join_recipients;
+use crate::service::mysql::binlog::{parse_xtrabackup_binlog_info, parse_xtrabackup_info};
 use crate::service::mysql::config::XtraBackupConfig;
 use crate::service::mysql::database::MysqlBackupRow;
 use crate::service::mysql::mysql_service::MySQLService;
-use crate::utils::get_size;
+use crate::utils::{get_size, ToolError};
+
+/// The contents of `xtrabackup_checkpoints`.
+#[derive(Debug, Default, PartialEq)]
+pub struct XtraBackupCheckpoints {
+    pub backup_type: String,
+    pub from_lsn: i64,
+    pub to_lsn: i64,
+    pub last_lsn: i64,
+}
+
+pub fn parse_xtrabackup_checkpoints(contents: &str) -> Option<XtraBackupCheckpoints> {
+    let mut checkpoints = XtraBackupCheckpoints::default();
+    let mut found = 0;
+    for line in contents.lines() {
+        let Some((key, value)) = line.split_once('=') else { continue };
+        let value = value.trim();
+        match key.trim() {
+            "backup_type" => checkpoints.backup_type = value.to_string(),
+            "from_lsn" => checkpoints.from_lsn = value.parse().ok()?,
+            "to_lsn" => checkpoints.to_lsn = value.parse().ok()?,
+            "last_lsn" => checkpoints.last_lsn = value.parse().ok()?,
+            _ => continue
+        }
+        found += 1;
+    }
+    (found == 4).then_some(checkpoints)
+}
+
+/// Checks that xtrabackup exited cleanly and reported success, returning why not otherwise.
+fn check_xtrabackup(status: ExitStatus, stderr: &str) -> Result<(), String> {
+    if !status.success() {
+        return Err(format!("xtrabackup exited with {}", status));
+    }
+    // xtrabackup logs this as its very last line once everything was copied.
+    if !stderr.contains("completed OK!") {
+        return Err("xtrabackup did not report \"completed OK!\"".to_string());
+    }
+    Ok(())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum XtraBackupMode {
+    Full,
+    Incremental
+}
+
+impl XtraBackupMode {
+    /// The mode a plain `interval` run uses.
+    pub fn from_config(mysql_config: &XtraBackupConfig) -> XtraBackupMode {
+        if mysql_config.incremental.unwrap_or(false) && mysql_config.incremental_interval.is_none() {
+            XtraBackupMode::Incremental
+        } else {
+            XtraBackupMode::Full
+        }
+    }
+}
 
 #[async_trait]
 pub trait XtraBackupRunner {
-    async fn do_xtrabackup(&self, mysql_config: &XtraBackupConfig) -> Result<(), Box<dyn std::error::Error>>;
+    async fn do_xtrabackup(&self, mysql_config: &XtraBackupConfig, mode: XtraBackupMode, run_uuid: Option<Uuid>) -> Result<(), Box<dyn std::error::Error>>;
+
+    async fn find_incremental_base(&self, mysql_config: &XtraBackupConfig) -> Result<Option<MysqlBackupRow>, Box<dyn std::error::Error>>;
 }
 
 #[async_trait]
 impl XtraBackupRunner for MySQLService {
-    async fn do_xtrabackup(&self, mysql_config: &XtraBackupConfig) -> Result<(), Box<dyn std::error::Error>> {
-        if let Some(config) = &self.config.backup {
+    async fn do_xtrabackup(&self, mysql_config: &XtraBackupConfig, mode: XtraBackupMode, run_uuid: Option<Uuid>) -> Result<(), Box<dyn std::error::Error>> {
+        if let Some(config) = &self.backup {
             let defaults = self.get_defaults_file().await?;
             let defaults_path = defaults.path();
             debug!("Creating xtrabackup using {} defaults file.", defaults_path.to_str().unwrap());
@@ -29,7 +91,7 @@ impl XtraBackupRunner for MySQLService {
             // Create the current backup uuid.
             let backup_uuid = Uuid::new_v7(Timestamp::now(NoContext));
             let mut base_uuid: Option<Uuid> = None;
-            let pool = DB_POOL.get().unwrap();
+            let catalog = CATALOG.get().unwrap();
 
             // Figure out the target directory.
             let current_date = Local::now().format("%Y-%m-%d").to_string();
@@ -38,6 +100,10 @@ impl XtraBackupRunner for MySQLService {
             fs::create_dir_all(target_dir.clone()).await?;
             debug!("Backup base directory: {}", target_dir.to_str().unwrap());
 
+            // Every backup gets its own directory, xtrabackup refuses to write into a non-empty one.
+            target_dir.push(backup_uuid.to_string());
+            debug!("Target directory: {}", target_dir.to_str().unwrap());
+
             let command_path = which("xtrabackup")?;
             let mut cmd = Command::new(command_path);
             cmd.arg(format!("--defaults-file={}", defaults_path.to_str().unwrap()));
@@ -68,25 +134,14 @@ impl XtraBackupRunner for MySQLService {
             }
 
             // Now if we are doing an incremental backup, we will want to handle it a little differently.
-            if let Some(incremental) = mysql_config.incremental {
-                if incremental {
-                    // Since we are creating an incremental backup, we just have to push the uuid into target directory.
-                    target_dir.push(backup_uuid.to_string());
-                    debug!("Target directory: {}", target_dir.to_str().unwrap());
-
-                    // We have to figure out the base directory.
-                    // how exactly do we figure out the base uuid???????????????????????????
-                    // how about we just create that position index inside the directory and just track it there?
-                    let previous_backup: Option<MysqlBackupRow> = sqlx::query_as("SELECT * FROM backups WHERE DATE(created_at) = $1 AND \"type\" = 1 ORDER BY uuid DESC")
-                        .bind(Utc::now().date_naive())
-                        .fetch_optional(pool)
-                        .await?;
-
-                    if let Some(backup_row) = previous_backup {
+            if mode == XtraBackupMode::Incremental {
+                match self.find_incremental_base(mysql_config).await? {
+                    Some(backup_row) => {
                         debug!("Previous backup found {} in {}.", backup_row.uuid, backup_row.path);
                         cmd.arg(format!("--incremental-basedir={}", backup_row.path));
                         base_uuid = Some(backup_row.uuid);
                     }
+                    None => info!("No usable base for an incremental backup of {}, taking a full backup.", self.name)
                 }
             }
 
@@ -94,30 +149,171 @@ impl XtraBackupRunner for MySQLService {
             cmd.arg(format!("--target-dir={}", target_dir.to_str().unwrap()));
 
             // Run the command and expect output.
-            let status = cmd.stdout(Stdio::null()).status().await?;
-            if status.success() {
-                debug!("-> Dumped!");
-
-                // Store it in the database.
-                {
-                    let path_str = target_dir.to_str().unwrap();
-                    let size = get_size(target_dir.clone()).unwrap() as i64;
-                    let created_at = Utc::now().naive_utc();
-                    let result = sqlx::query("INSERT INTO backups (uuid, base_uuid, type, path, size, created_at) VALUES ($1, $2, 1, $4, $5, $6)")
-                        .bind(backup_uuid)
-                        .bind(base_uuid)
-                        .bind(path_str)
-                        .bind(size)
-                        .bind(created_at)
-                        .execute(pool).await?;
-
-                    println!("Test: {:?}", result);
+            let recipients = self.encryption_recipients()?;
+            let result = async {
+                if config.compression.is_some() || !recipients.is_empty() {
+                    // Stream into the compressor and encryptor, the checkpoints still land next to the stream so the backup can be an incremental base.
+                    cmd.arg("--stream=xbstream");
+                    cmd.arg(format!("--extra-lsndir={}", target_dir.to_str().unwrap()));
+                    fs::create_dir_all(&target_dir).await?;
+                    let stream_path = target_dir.join(format!("backup.{}", artifact_extension("xbstream", config.compression.as_ref(), !recipients.is_empty())));
+                    let output = run_to_artifact(&mut cmd, &stream_path, config.compression.as_ref(), &recipients).await?;
+                    Ok((output.status, output.stderr))
+                } else {
+                    let mut child = cmd.stdout(Stdio::null()).stderr(Stdio::piped()).kill_on_drop(true).spawn()?;
+                    let stderr = read_stderr(child.stderr.take().unwrap()).await?;
+                    Ok::<_, std::io::Error>((child.wait().await?, stderr))
+                }
+            }.await;
+            let (status, stderr) = match result {
+                Ok(output) => output,
+                Err(error) => {
+                    // Whatever xtrabackup got to write is neither restorable nor an incremental base.
+                    if target_dir.is_dir() {
+                        fs::remove_dir_all(&target_dir).await?;
+                    }
+                    return Err(error.into());
+                }
+            };
+            if let Err(reason) = check_xtrabackup(status, &stderr) {
+                error!("-> Failed to back up {}: {}", self.name, reason);
+                // A partial backup can neither be restored nor serve as an incremental base.
+                if target_dir.is_dir() {
+                    fs::remove_dir_all(&target_dir).await?;
+                }
+                return Err(ToolError::new(format!("xtrabackup failed for {}: {}", self.name, reason), stderr).into());
+            }
+            debug!("-> Dumped!");
+
+            // Store it in the database.
+            {
+                // Hash every file first, so the manifest is part of the recorded size and of the upload.
+                let checksum = write_manifest(&target_dir).await?;
+                let path_str = target_dir.to_str().unwrap();
+                let size = get_size(target_dir.clone()).unwrap() as i64;
+                let created_at = Utc::now().naive_utc();
+
+                // xtrabackup writes the binary log coordinates it copied up to next to the data.
+                let binlog_info_path = target_dir.join("xtrabackup_binlog_info");
+                let info_path = target_dir.join("xtrabackup_info");
+                let coordinates = if binlog_info_path.is_file() {
+                    parse_xtrabackup_binlog_info(&fs::read_to_string(binlog_info_path).await?)
+                } else if info_path.is_file() {
+                    parse_xtrabackup_info(&fs::read_to_string(info_path).await?)
+                } else {
+                    None
+                };
+
+                // As well as the LSN range it covers, which is what incrementals are chained by.
+                let checkpoints_path = target_dir.join("xtrabackup_checkpoints");
+                let checkpoints = if checkpoints_path.is_file() {
+                    parse_xtrabackup_checkpoints(&fs::read_to_string(checkpoints_path).await?)
+                } else {
+                    None
+                };
+                if checkpoints.is_none() {
+                    warn!("No xtrabackup_checkpoints found in {}, the backup cannot be used as an incremental base.", path_str);
                 }
-            } else {
-                debug!("-> Failed to dump!");
+
+                let backup = MysqlBackupRow {
+                    uuid: backup_uuid,
+                    base_uuid,
+                    backup_type: 1,
+                    path: path_str.to_string(),
+                    size,
+                    created_at,
+                    database_name: None,
+                    binlog_file: coordinates.as_ref().map(|coordinates| coordinates.file.clone()),
+                    binlog_position: coordinates.as_ref().map(|coordinates| coordinates.position),
+                    gtid_executed: coordinates.and_then(|coordinates| coordinates.gtid_executed),
+                    service: Some(self.name.clone()),
+                    checkpoint_type: checkpoints.as_ref().map(|checkpoints| checkpoints.backup_type.clone()),
+                    from_lsn: checkpoints.as_ref().map(|checkpoints| checkpoints.from_lsn),
+                    to_lsn: checkpoints.as_ref().map(|checkpoints| checkpoints.to_lsn),
+                    last_lsn: checkpoints.as_ref().map(|checkpoints| checkpoints.last_lsn),
+                    compression: config.compression.as_ref().map(|compression| compression.codec.name().to_string()),
+                    encryption_recipients: join_recipients(&recipients),
+                    remote_key: None,
+                    checksum: Some(checksum),
+                    run_uuid,
+                    host: Some(catalog.host().to_string()),
+                    job: self.job.clone()
+                };
+                catalog.insert_backup(&backup).await?;
+                debug!("Backup recorded: {:?}", backup);
+
+                // Recorded first, so a failed upload still leaves a backup retention and restores know about.
+                self.store_backup(catalog.as_ref(), backup_uuid, &target_dir).await?;
             }
         }
 
         Ok(())
     }
-}
\ No newline at end of file
+
+    async fn find_incremental_base(&self, mysql_config: &XtraBackupConfig) -> Result<Option<MysqlBackupRow>, Box<dyn std::error::Error>> {
+        let catalog = CATALOG.get().unwrap().as_ref();
+        // The backup which got furthest into the redo log, regardless of when it was taken.
+        let previous_backup = catalog.find_latest_xtrabackup(&self.name, self.job.as_deref()).await?;
+        let Some(previous_backup) = previous_backup else { return Ok(None) };
+
+        // The full backup the latest chain starts from, along with everything taken on top of it.
+        let chain = match MysqlBackupRow::find_chain(catalog, previous_backup.uuid).await {
+            Ok(chain) => chain,
+            Err(error) => {
+                warn!("Chain of {} is unusable, error: {}", previous_backup.uuid, error);
+                return Ok(None);
+            }
+        };
+        if let Err(error) = MysqlBackupRow::validate_chain(&chain) {
+            warn!("Chain of {} is not continuous, error: {}", previous_backup.uuid, error);
+            return Ok(None);
+        }
+        if chain.iter().any(|backup| !PathBuf::from(&backup.path).is_dir()) {
+            warn!("Chain of {} is missing from disk.", previous_backup.uuid);
+            return Ok(None);
+        }
+        let full_backup = chain.into_iter().next().unwrap();
+        let chain_length = MysqlBackupRow::find_dependents(catalog, full_backup.uuid).await?.len() + 1;
+        if let Some(max_chain_length) = mysql_config.max_chain_length {
+            if chain_length >= max_chain_length as usize {
+                info!("Chain of {} reached {} backup(s), starting a new one.", full_backup.uuid, chain_length);
+                return Ok(None);
+            }
+        }
+
+        if mysql_config.cumulative.unwrap_or(false) {
+            Ok(Some(full_backup))
+        } else {
+            Ok(Some(previous_backup))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_xtrabackup_checkpoints() {
+        let contents = "backup_type = incremental\nfrom_lsn = 18153673\nto_lsn = 18153999\nlast_lsn = 18154008\nflushed_lsn = 18154008\n";
+        let checkpoints = parse_xtrabackup_checkpoints(contents).unwrap();
+        assert_eq!(checkpoints, XtraBackupCheckpoints {
+            backup_type: "incremental".to_string(),
+            from_lsn: 18153673,
+            to_lsn: 18153999,
+            last_lsn: 18154008,
+        });
+
+        assert!(parse_xtrabackup_checkpoints("backup_type = full-backuped\nfrom_lsn = 0\n").is_none());
+        assert!(parse_xtrabackup_checkpoints("backup_type = full-backuped\nfrom_lsn = x\nto_lsn = 1\nlast_lsn = 1\n").is_none());
+    }
+
+    #[test]
+    fn test_check_xtrabackup() {
+        use std::os::unix::process::ExitStatusExt;
+        let ok = ExitStatus::from_raw(0);
+        assert!(check_xtrabackup(ok, "2024-04-16T18:30:00 0 [Note] [MY-011825] [Xtrabackup] completed OK!\n").is_ok());
+        assert!(check_xtrabackup(ok, "2024-04-16T18:30:00 0 [ERROR] [MY-011825] [Xtrabackup] read failed\n").is_err());
+        assert!(check_xtrabackup(ExitStatus::from_raw(1 << 8), "completed OK!\n").is_err());
+    }
+}
@@ -2,10 +2,25 @@ use std::any::Any;
 use std::sync::Arc;
 use async_trait::async_trait;
 use tokio_cron_scheduler::JobScheduler;
+use uuid::Uuid;
 
 #[async_trait]
 pub trait Service: Send + Sync + Any {
+    fn name(&self) -> &str;
+
+    /// The names of the service's backup jobs, empty when it only has the unnamed one.
+    fn jobs(&self) -> Vec<String>;
+
+    /// Whether a backup is being taken right now.
+    async fn is_running(&self) -> bool;
+
     async fn update(&self) -> Result<(), Box<dyn std::error::Error>>;
+
+    /// Stops whatever the service runs in the background, e.g. binary log archivers.
+    async fn shutdown(&self);
+
+    /// Starts a manual run of a job in the background unless it is already running, returning the run's uuid.
+    async fn trigger(self: Arc<Self>, job: Option<&str>) -> Result<Option<Uuid>, Box<dyn std::error::Error>>;
 }
 
 #[async_trait]
@@ -0,0 +1,46 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct S3StorageConfig {
+    /// Custom endpoint for S3-compatible stores such as MinIO, defaults to AWS.
+    pub endpoint: Option<String>,
+    pub region: Option<String>,
+    pub bucket: String,
+    pub prefix: Option<String>,
+    /// Credentials, the usual `AWS_*` environment variables are used when left out.
+    pub access_key_id: Option<String>,
+    pub secret_access_key: Option<String>,
+    /// Address the bucket as `<endpoint>/<bucket>` instead of `<bucket>.<endpoint>`, defaults to true.
+    pub path_style: Option<bool>
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SftpStorageConfig {
+    pub host: String,
+    pub port: Option<u16>,
+    pub username: String,
+    /// Private key used to log in, password logins are not supported.
+    pub key_file: String,
+    pub key_passphrase: Option<String>,
+    /// The host key has to be listed in this file, defaults to `~/.ssh/known_hosts`.
+    pub known_hosts: Option<String>,
+    /// Remote directory everything is stored below.
+    pub path: String
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(tag = "type")]
+pub enum StorageBackend {
+    #[serde(rename = "s3")]
+    S3(S3StorageConfig),
+    #[serde(rename = "sftp")]
+    Sftp(SftpStorageConfig)
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct StorageConfig {
+    #[serde(flatten)]
+    pub backend: StorageBackend,
+    /// Remove the local copy once it has been uploaded.
+    pub delete_local: Option<bool>
+}
@@ -0,0 +1,29 @@
+pub mod config;
+pub mod s3;
+pub mod sftp;
+
+use std::path::Path;
+use async_trait::async_trait;
+use crate::storage::config::{StorageBackend, StorageConfig};
+use crate::storage::s3::S3Storage;
+use crate::storage::sftp::SftpStorage;
+
+/// A place finished backup artifacts are copied to, keyed by a `/` separated path.
+#[async_trait]
+pub trait Storage: Send + Sync {
+    /// Uploads a file, or every file below a directory, to `key`.
+    async fn upload(&self, local_path: &Path, key: &str) -> Result<(), Box<dyn std::error::Error>>;
+
+    /// Downloads whatever `upload` stored under `key` back into `local_path`.
+    async fn download(&self, key: &str, local_path: &Path) -> Result<(), Box<dyn std::error::Error>>;
+
+    /// Deletes everything stored under `key`.
+    async fn delete(&self, key: &str) -> Result<(), Box<dyn std::error::Error>>;
+}
+
+pub fn create_storage(config: &StorageConfig) -> Result<Box<dyn Storage>, Box<dyn std::error::Error>> {
+    match &config.backend {
+        StorageBackend::S3(s3_config) => Ok(Box::new(S3Storage::from_config(s3_config)?)),
+        StorageBackend::Sftp(sftp_config) => Ok(Box::new(SftpStorage::new(sftp_config.clone())))
+    }
+}
@@ -0,0 +1,246 @@
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use async_trait::async_trait;
+use futures::stream::FuturesUnordered;
+use futures::StreamExt;
+use log::{debug, warn};
+use object_store::aws::AmazonS3Builder;
+use object_store::path::Path as ObjectPath;
+use object_store::{MultipartUpload, ObjectStore};
+use tokio::fs;
+use tokio::fs::File;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use crate::storage::config::S3StorageConfig;
+use crate::storage::Storage;
+
+/// Files up to this size are uploaded in one request, larger ones in parts of this size.
+const PART_SIZE: usize = 16 * 1024 * 1024;
+
+/// How many parts are uploaded at the same time.
+const MAX_CONCURRENT_PARTS: usize = 4;
+
+pub struct S3Storage {
+    store: Arc<dyn ObjectStore>,
+    prefix: Option<String>
+}
+
+impl S3Storage {
+    pub fn new(store: Arc<dyn ObjectStore>, prefix: Option<String>) -> S3Storage {
+        S3Storage { store, prefix }
+    }
+
+    pub fn from_config(config: &S3StorageConfig) -> Result<S3Storage, Box<dyn std::error::Error>> {
+        let mut builder = AmazonS3Builder::from_env()
+            .with_bucket_name(&config.bucket)
+            .with_virtual_hosted_style_request(!config.path_style.unwrap_or(true));
+        if let Some(region) = &config.region {
+            builder = builder.with_region(region);
+        }
+        if let Some(endpoint) = &config.endpoint {
+            builder = builder.with_endpoint(endpoint).with_allow_http(endpoint.starts_with("http://"));
+        }
+        if let Some(access_key_id) = &config.access_key_id {
+            builder = builder.with_access_key_id(access_key_id);
+        }
+        if let Some(secret_access_key) = &config.secret_access_key {
+            builder = builder.with_secret_access_key(secret_access_key);
+        }
+        Ok(S3Storage::new(Arc::new(builder.build()?), config.prefix.clone()))
+    }
+
+    fn location(&self, key: &str) -> Result<ObjectPath, Box<dyn std::error::Error>> {
+        let key = match &self.prefix {
+            Some(prefix) => format!("{}/{}", prefix.trim_matches('/'), key),
+            None => key.to_string()
+        };
+        Ok(ObjectPath::parse(key)?)
+    }
+
+    async fn upload_file(&self, local_path: &Path, location: &ObjectPath) -> Result<(), Box<dyn std::error::Error>> {
+        debug!("Uploading {} to {}", local_path.to_str().unwrap(), location);
+        let mut file = File::open(local_path).await?;
+        if file.metadata().await?.len() <= PART_SIZE as u64 {
+            let mut contents = vec![];
+            file.read_to_end(&mut contents).await?;
+            self.store.put(location, contents.into()).await?;
+            return Ok(());
+        }
+
+        // A failed upload is aborted, otherwise the parts already uploaded linger in the bucket.
+        let mut upload = self.store.put_multipart(location).await?;
+        let result = match upload_parts(upload.as_mut(), &mut file).await {
+            Ok(_) => upload.complete().await.map(|_| ()).map_err(|error| error.into()),
+            Err(error) => Err(error)
+        };
+        if let Err(error) = result {
+            if let Err(abort_error) = upload.abort().await {
+                warn!("Failed to abort the upload to {}, error: {}", location, abort_error);
+            }
+            return Err(error as Box<dyn std::error::Error>);
+        }
+        Ok(())
+    }
+
+    async fn download_file(&self, location: &ObjectPath, local_path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+        debug!("Downloading {} to {}", location, local_path.to_str().unwrap());
+        if let Some(parent) = local_path.parent() {
+            fs::create_dir_all(parent).await?;
+        }
+        let mut file = File::create(local_path).await?;
+        let mut stream = self.store.get(location).await?.into_stream();
+        while let Some(chunk) = stream.next().await {
+            file.write_all(&chunk?).await?;
+        }
+        file.flush().await?;
+        Ok(())
+    }
+}
+
+/// Uploads the file in parts of `PART_SIZE`, a few of them at the same time.
+async fn upload_parts(upload: &mut dyn MultipartUpload, file: &mut File) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let mut pending = FuturesUnordered::new();
+    loop {
+        // Every part but the last has to be full, S3 rejects smaller ones.
+        let mut part = Vec::with_capacity(PART_SIZE);
+        (&mut *file).take(PART_SIZE as u64).read_to_end(&mut part).await?;
+        if part.is_empty() {
+            break;
+        }
+        if pending.len() >= MAX_CONCURRENT_PARTS {
+            pending.next().await.unwrap()?;
+        }
+        pending.push(upload.put_part(part.into()));
+    }
+    while let Some(result) = pending.next().await {
+        result?;
+    }
+    Ok(())
+}
+
+/// Lists every file below `directory` as paths relative to it.
+async fn list_files(directory: &Path) -> Result<Vec<PathBuf>, std::io::Error> {
+    let mut files = vec![];
+    let mut pending = vec![PathBuf::new()];
+    while let Some(relative) = pending.pop() {
+        let mut entries = fs::read_dir(directory.join(&relative)).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            let entry_relative = relative.join(entry.file_name());
+            if entry.file_type().await?.is_dir() {
+                pending.push(entry_relative);
+            } else {
+                files.push(entry_relative);
+            }
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+#[async_trait]
+impl Storage for S3Storage {
+    async fn upload(&self, local_path: &Path, key: &str) -> Result<(), Box<dyn std::error::Error>> {
+        if !local_path.is_dir() {
+            let location = self.location(key)?;
+            return self.upload_file(local_path, &location).await;
+        }
+
+        // Directories become one object per file below the key.
+        for relative in list_files(local_path).await? {
+            let relative_key = relative.iter().map(|part| part.to_str().unwrap()).collect::<Vec<&str>>().join("/");
+            let location = self.location(&format!("{}/{}", key, relative_key))?;
+            self.upload_file(&local_path.join(&relative), &location).await?;
+        }
+        Ok(())
+    }
+
+    async fn download(&self, key: &str, local_path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+        let location = self.location(key)?;
+        let objects = self.store.list(Some(&location)).map(|meta| meta.map(|meta| meta.location)).collect::<Vec<_>>().await;
+        let objects = objects.into_iter().collect::<Result<Vec<ObjectPath>, _>>()?;
+
+        // A single object at the key itself is a file, anything below it a directory.
+        let children = objects.iter().filter(|object| **object != location).collect::<Vec<&ObjectPath>>();
+        if children.is_empty() {
+            return self.download_file(&location, local_path).await;
+        }
+        for object in children {
+            let relative = object.prefix_match(&location).unwrap().map(|part| part.as_ref().to_string()).collect::<Vec<String>>();
+            self.download_file(object, &local_path.join(relative.join("/"))).await?;
+        }
+        Ok(())
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let location = self.location(key)?;
+        // Listing only returns what is below the key, a file lives at the key itself.
+        match self.store.delete(&location).await {
+            Ok(_) | Err(object_store::Error::NotFound { .. }) => {}
+            Err(error) => return Err(error.into())
+        }
+        let objects = self.store.list(Some(&location)).collect::<Vec<_>>().await;
+        for object in objects {
+            let object = object?;
+            if object.location != location {
+                self.store.delete(&object.location).await?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use object_store::memory::InMemory;
+    use tempfile::tempdir;
+
+    #[tokio::test]
+    async fn test_upload_download_delete() {
+        let store = Arc::new(InMemory::new());
+        let storage = S3Storage::new(store.clone(), Some("/backups/".to_string()));
+        let dir = tempdir().unwrap();
+
+        // A single file.
+        let file_path = dir.path().join("auth.sql");
+        std::fs::write(&file_path, "CREATE TABLE users (id INT);").unwrap();
+        storage.upload(&file_path, "mysql-r1/auth.sql").await.unwrap();
+        assert!(store.head(&ObjectPath::parse("backups/mysql-r1/auth.sql").unwrap()).await.is_ok());
+
+        // A directory, such as an xtrabackup.
+        let backup_dir = dir.path().join("xtrabackup");
+        std::fs::create_dir_all(backup_dir.join("auth")).unwrap();
+        std::fs::write(backup_dir.join("xtrabackup_checkpoints"), "backup_type = full-backuped").unwrap();
+        std::fs::write(backup_dir.join("auth").join("users.ibd"), "data").unwrap();
+        storage.upload(&backup_dir, "mysql-r1/xtrabackup").await.unwrap();
+
+        let restore_dir = dir.path().join("restore");
+        storage.download("mysql-r1/xtrabackup", &restore_dir).await.unwrap();
+        assert_eq!(std::fs::read_to_string(restore_dir.join("auth").join("users.ibd")).unwrap(), "data");
+        assert_eq!(list_files(&restore_dir).await.unwrap().len(), 2);
+
+        let restore_file = dir.path().join("restored.sql");
+        storage.download("mysql-r1/auth.sql", &restore_file).await.unwrap();
+        assert_eq!(std::fs::read_to_string(&restore_file).unwrap(), "CREATE TABLE users (id INT);");
+
+        storage.delete("mysql-r1/xtrabackup").await.unwrap();
+        storage.delete("mysql-r1/auth.sql").await.unwrap();
+        assert_eq!(store.list(None).collect::<Vec<_>>().await.len(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_upload_in_parts() {
+        let store = Arc::new(InMemory::new());
+        let storage = S3Storage::new(store.clone(), None);
+        let dir = tempdir().unwrap();
+
+        // Two full parts and a short last one.
+        let contents = (0..PART_SIZE * 2 + 1).map(|index| (index % 251) as u8).collect::<Vec<u8>>();
+        let file_path = dir.path().join("backup.xbstream");
+        std::fs::write(&file_path, &contents).unwrap();
+        storage.upload(&file_path, "mysql-r1/backup.xbstream").await.unwrap();
+
+        let restore_file = dir.path().join("restored.xbstream");
+        storage.download("mysql-r1/backup.xbstream", &restore_file).await.unwrap();
+        assert!(std::fs::read(&restore_file).unwrap() == contents);
+    }
+}
@@ -0,0 +1,178 @@
+use std::io;
+use std::net::TcpStream;
+use std::path::{Path, PathBuf};
+use async_trait::async_trait;
+use log::debug;
+use ssh2::{CheckResult, KnownHostFileKind, Session, Sftp};
+use crate::storage::config::SftpStorageConfig;
+use crate::storage::Storage;
+
+/// libssh2 is blocking, so every operation opens its own session on the blocking pool.
+pub struct SftpStorage {
+    config: SftpStorageConfig
+}
+
+impl SftpStorage {
+    pub fn new(config: SftpStorageConfig) -> SftpStorage {
+        SftpStorage { config }
+    }
+
+    fn remote_path(&self, key: &str) -> PathBuf {
+        key.split('/').filter(|part| !part.is_empty()).fold(PathBuf::from(&self.config.path), |path, part| path.join(part))
+    }
+
+    async fn run<T, F>(&self, operation: F) -> Result<T, Box<dyn std::error::Error>>
+    where
+        T: Send + 'static,
+        F: FnOnce(&Sftp) -> Result<T, String> + Send + 'static
+    {
+        let config = self.config.clone();
+        let result = tokio::task::spawn_blocking(move || {
+            let sftp = connect(&config)?;
+            operation(&sftp)
+        }).await?;
+        Ok(result?)
+    }
+}
+
+/// Opens an SFTP session, refusing hosts whose key is not in `known_hosts`.
+fn connect(config: &SftpStorageConfig) -> Result<Sftp, String> {
+    let port = config.port.unwrap_or(22);
+    let stream = TcpStream::connect((config.host.as_str(), port)).map_err(|error| format!("Unable to connect to {}:{}: {}", config.host, port, error))?;
+    let mut session = Session::new().map_err(|error| error.to_string())?;
+    session.set_tcp_stream(stream);
+    session.handshake().map_err(|error| format!("SSH handshake with {} failed: {}", config.host, error))?;
+
+    let known_hosts_path = match &config.known_hosts {
+        Some(known_hosts) => PathBuf::from(known_hosts),
+        None => PathBuf::from(std::env::var("HOME").unwrap_or_default()).join(".ssh").join("known_hosts")
+    };
+    let mut known_hosts = session.known_hosts().map_err(|error| error.to_string())?;
+    known_hosts.read_file(&known_hosts_path, KnownHostFileKind::OpenSSH)
+        .map_err(|error| format!("Unable to read {}: {}", known_hosts_path.to_str().unwrap(), error))?;
+    let (host_key, _) = session.host_key().ok_or(format!("{} did not send a host key.", config.host))?;
+    match known_hosts.check_port(&config.host, port, host_key) {
+        CheckResult::Match => {}
+        CheckResult::Mismatch => return Err(format!("Host key of {} does not match {}.", config.host, known_hosts_path.to_str().unwrap())),
+        CheckResult::NotFound => return Err(format!("Host key of {} is not listed in {}.", config.host, known_hosts_path.to_str().unwrap())),
+        CheckResult::Failure => return Err(format!("Unable to check the host key of {}.", config.host))
+    }
+
+    session.userauth_pubkey_file(&config.username, None, Path::new(&config.key_file), config.key_passphrase.as_deref())
+        .map_err(|error| format!("Authentication as {} on {} failed: {}", config.username, config.host, error))?;
+    session.sftp().map_err(|error| error.to_string())
+}
+
+/// Creates the remote directory along with its missing parents.
+fn create_dir_all(sftp: &Sftp, path: &Path) -> Result<(), String> {
+    let mut current = PathBuf::new();
+    for part in path.iter() {
+        current.push(part);
+        if sftp.stat(&current).is_err() {
+            sftp.mkdir(&current, 0o750).map_err(|error| format!("Unable to create {}: {}", current.to_str().unwrap(), error))?;
+        }
+    }
+    Ok(())
+}
+
+fn upload_file(sftp: &Sftp, local_path: &Path, remote_path: &Path) -> Result<(), String> {
+    debug!("Uploading {} to {}", local_path.to_str().unwrap(), remote_path.to_str().unwrap());
+    if let Some(parent) = remote_path.parent() {
+        create_dir_all(sftp, parent)?;
+    }
+
+    // Write next to the target and rename, so an interrupted upload never looks complete.
+    let partial_path = remote_path.with_file_name(format!("{}.partial", remote_path.file_name().unwrap().to_str().unwrap()));
+    let mut local = std::fs::File::open(local_path).map_err(|error| error.to_string())?;
+    let mut remote = sftp.create(&partial_path).map_err(|error| format!("Unable to create {}: {}", partial_path.to_str().unwrap(), error))?;
+    io::copy(&mut local, &mut remote).map_err(|error| format!("Unable to upload {}: {}", local_path.to_str().unwrap(), error))?;
+    drop(remote);
+    if sftp.stat(remote_path).is_ok() {
+        sftp.unlink(remote_path).map_err(|error| error.to_string())?;
+    }
+    sftp.rename(&partial_path, remote_path, None).map_err(|error| error.to_string())
+}
+
+fn upload(sftp: &Sftp, local_path: &Path, remote_path: &Path) -> Result<(), String> {
+    if !local_path.is_dir() {
+        return upload_file(sftp, local_path, remote_path);
+    }
+    create_dir_all(sftp, remote_path)?;
+    for entry in std::fs::read_dir(local_path).map_err(|error| error.to_string())? {
+        let entry = entry.map_err(|error| error.to_string())?;
+        upload(sftp, &entry.path(), &remote_path.join(entry.file_name()))?;
+    }
+    Ok(())
+}
+
+fn download(sftp: &Sftp, remote_path: &Path, local_path: &Path) -> Result<(), String> {
+    let stat = sftp.stat(remote_path).map_err(|error| format!("Unable to find {}: {}", remote_path.to_str().unwrap(), error))?;
+    if stat.is_dir() {
+        std::fs::create_dir_all(local_path).map_err(|error| error.to_string())?;
+        for (child, _) in sftp.readdir(remote_path).map_err(|error| error.to_string())? {
+            download(sftp, &child, &local_path.join(child.file_name().unwrap()))?;
+        }
+        return Ok(());
+    }
+
+    debug!("Downloading {} to {}", remote_path.to_str().unwrap(), local_path.to_str().unwrap());
+    if let Some(parent) = local_path.parent() {
+        std::fs::create_dir_all(parent).map_err(|error| error.to_string())?;
+    }
+    let mut remote = sftp.open(remote_path).map_err(|error| error.to_string())?;
+    let mut local = std::fs::File::create(local_path).map_err(|error| error.to_string())?;
+    io::copy(&mut remote, &mut local).map_err(|error| format!("Unable to download {}: {}", remote_path.to_str().unwrap(), error))?;
+    Ok(())
+}
+
+fn delete(sftp: &Sftp, remote_path: &Path) -> Result<(), String> {
+    let Ok(stat) = sftp.stat(remote_path) else { return Ok(()) };
+    if stat.is_dir() {
+        for (child, _) in sftp.readdir(remote_path).map_err(|error| error.to_string())? {
+            delete(sftp, &child)?;
+        }
+        sftp.rmdir(remote_path).map_err(|error| format!("Unable to remove {}: {}", remote_path.to_str().unwrap(), error))
+    } else {
+        sftp.unlink(remote_path).map_err(|error| format!("Unable to remove {}: {}", remote_path.to_str().unwrap(), error))
+    }
+}
+
+#[async_trait]
+impl Storage for SftpStorage {
+    async fn upload(&self, local_path: &Path, key: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let local_path = local_path.to_path_buf();
+        let remote_path = self.remote_path(key);
+        self.run(move |sftp| upload(sftp, &local_path, &remote_path)).await
+    }
+
+    async fn download(&self, key: &str, local_path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+        let local_path = local_path.to_path_buf();
+        let remote_path = self.remote_path(key);
+        self.run(move |sftp| download(sftp, &remote_path, &local_path)).await
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let remote_path = self.remote_path(key);
+        self.run(move |sftp| delete(sftp, &remote_path)).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_remote_path() {
+        let storage = SftpStorage::new(SftpStorageConfig {
+            host: "vault".to_string(),
+            port: None,
+            username: "backup".to_string(),
+            key_file: "/etc/backup/id_ed25519".to_string(),
+            key_passphrase: None,
+            known_hosts: None,
+            path: "/srv/vault".to_string()
+        });
+        assert_eq!(storage.remote_path("mysql-r1/2024-04-16/auth.sql"), PathBuf::from("/srv/vault/mysql-r1/2024-04-16/auth.sql"));
+        assert_eq!(storage.remote_path("/mysql-r1//auth.sql"), PathBuf::from("/srv/vault/mysql-r1/auth.sql"));
+    }
+}
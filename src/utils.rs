@@ -1,6 +1,32 @@
+use std::fmt;
 use std::fs;
 use std::path::Path;
 
+/// A backup tool which failed or left incomplete output, along with what it wrote to stderr.
+#[derive(Debug)]
+pub struct ToolError {
+    pub message: String,
+    pub stderr: String
+}
+
+impl ToolError {
+    pub fn new(message: String, stderr: String) -> ToolError {
+        ToolError { message, stderr }
+    }
+}
+
+impl fmt::Display for ToolError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // The last line is usually the one explaining what went wrong.
+        match self.stderr.lines().rev().find(|line| !line.trim().is_empty()) {
+            Some(line) => write!(f, "{} ({})", self.message, line.trim()),
+            None => write!(f, "{}", self.message)
+        }
+    }
+}
+
+impl std::error::Error for ToolError {}
+
 pub fn get_size<P: AsRef<Path>>(path: P) -> Result<u64, std::io::Error> {
     let path = path.as_ref();
     let metadata = fs::metadata(path)?;
@@ -15,9 +41,64 @@ pub fn get_size<P: AsRef<Path>>(path: P) -> Result<u64, std::io::Error> {
         }
         Ok(total_size)
     } else {
-        Err(std::io::Error::new(
-            std::io::ErrorKind::Other,
-            "Not a file or directory",
-        ))
+        Err(std::io::Error::other("Not a file or directory"))
+    }
+}
+
+pub fn copy_dir<P: AsRef<Path>, Q: AsRef<Path>>(source: P, target: Q) -> Result<(), std::io::Error> {
+    let target = target.as_ref();
+    fs::create_dir_all(target)?;
+    for entry in fs::read_dir(source)? {
+        let entry = entry?;
+        let target_path = target.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            copy_dir(entry.path(), target_path)?;
+        } else {
+            fs::copy(entry.path(), target_path)?;
+        }
+    }
+    Ok(())
+}
+
+/// Formats a byte count for humans, e.g. `1.5 GiB`.
+pub fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} {}", bytes, UNITS[0])
+    } else {
+        format!("{:.1} {}", size, UNITS[unit])
+    }
+}
+
+/// Parses a duration such as `26h`, `90m` or `1d12h`, the units are `s`, `m`, `h`, `d` and `w`.
+pub fn parse_duration(value: &str) -> Result<std::time::Duration, String> {
+    let mut seconds = 0u64;
+    let mut number = String::new();
+    for character in value.trim().chars() {
+        if character.is_ascii_digit() {
+            number.push(character);
+            continue;
+        }
+        let unit = match character {
+            's' => 1,
+            'm' => 60,
+            'h' => 60 * 60,
+            'd' => 24 * 60 * 60,
+            'w' => 7 * 24 * 60 * 60,
+            _ => return Err(format!("\"{}\" is not a valid duration, unknown unit '{}'", value, character))
+        };
+        let count: u64 = number.parse().map_err(|_| format!("\"{}\" is not a valid duration, '{}' has no number", value, character))?;
+        seconds += count * unit;
+        number.clear();
+    }
+    if !number.is_empty() || seconds == 0 {
+        return Err(format!("\"{}\" is not a valid duration, expected e.g. \"26h\"", value));
     }
-}
\ No newline at end of file
+    Ok(std::time::Duration::from_secs(seconds))
+}
@@ -0,0 +1,186 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use log::{error, info, warn};
+use sqlx::types::chrono::{NaiveDateTime, Utc};
+use tokio::time::interval;
+use uuid::Uuid;
+use crate::catalog::Catalog;
+use crate::notification::{notify, Notification};
+use crate::notification::config::NotificationEvent;
+use crate::service::mysql::database::MysqlBackupRow;
+use crate::service::mysql::mysql_service::MySQLService;
+use crate::service::service::Service;
+use crate::utils::{format_size, parse_duration};
+
+/// How often the catalog is looked at.
+const CHECK_INTERVAL: Duration = Duration::from_secs(5 * 60);
+
+/// How many earlier runs a run's size is compared with by default.
+const DEFAULT_SIZE_WINDOW: usize = 7;
+
+/// What was already reported, so a condition alerts once instead of on every check.
+#[derive(Default)]
+struct Alerted {
+    stale: bool,
+    size_deviation: Option<Uuid>
+}
+
+/// How far a run's size is off the average of the runs of the same kind before it.
+#[derive(Debug, PartialEq)]
+pub struct SizeDeviation {
+    /// The run, or the backup for rows recorded before runs were.
+    pub run: Uuid,
+    pub size: i64,
+    pub average: f64,
+    /// Signed, a shrinking backup is negative.
+    pub percent: f64,
+    /// Indices of the run's backups.
+    pub backups: Vec<usize>
+}
+
+/// How old the newest backup is when that is more than `max_age`, a service without backups counts from `since`.
+pub fn stale_for(newest: Option<NaiveDateTime>, since: NaiveDateTime, max_age: Duration, now: NaiveDateTime) -> Option<Duration> {
+    let age = (now - newest.unwrap_or(since)).to_std().unwrap_or_default();
+    if age > max_age { Some(age) } else { None }
+}
+
+/// Compares the newest run in `backups`, which are sorted by creation time, with up to `window` runs of the same kind before it.
+/// A mysqldump run writes one backup per database, so runs are compared by the sum of theirs.
+pub fn size_deviation(backups: &[MysqlBackupRow], window: usize) -> Option<SizeDeviation> {
+    let mut runs: Vec<(Uuid, &str, i64, Vec<usize>)> = vec![];
+    for (index, backup) in backups.iter().enumerate() {
+        let run = backup.run_uuid.unwrap_or(backup.uuid);
+        match runs.iter_mut().find(|(uuid, ..)| *uuid == run) {
+            Some((_, _, size, indices)) => {
+                *size += backup.size;
+                indices.push(index);
+            }
+            None => runs.push((run, backup.kind(), backup.size, vec![index]))
+        }
+    }
+
+    let (run, kind, size, indices) = runs.pop()?;
+    let earlier = runs.iter().rev().filter(|(_, other_kind, ..)| *other_kind == kind).take(window).map(|(_, _, size, _)| *size).collect::<Vec<_>>();
+    if earlier.is_empty() {
+        return None;
+    }
+    let average = earlier.iter().sum::<i64>() as f64 / earlier.len() as f64;
+    if average <= 0.0 {
+        return None;
+    }
+    Some(SizeDeviation { run, size, average, percent: (size as f64 - average) / average * 100.0, backups: indices })
+}
+
+async fn check_service(service: &MySQLService, catalog: &dyn Catalog, since: NaiveDateTime, alerted: &mut Alerted) -> Result<(), sqlx::Error> {
+    let Some(backup_config) = &service.backup else { return Ok(()) };
+    let backups = catalog.find_local_backups(&service.name).await?
+        .into_iter()
+        .filter(|backup| backup.job == service.job)
+        .collect::<Vec<_>>();
+
+    if let Some(max_age) = &backup_config.max_age {
+        let Ok(max_age_duration) = parse_duration(max_age) else { return Ok(()) };
+        match stale_for(backups.last().map(|backup| backup.created_at), since, max_age_duration, Utc::now().naive_utc()) {
+            Some(age) if !alerted.stale => {
+                alerted.stale = true;
+                let detail = format!("the newest backup is {:.1}h old, max_age is {}", age.as_secs_f64() / 3600.0, max_age);
+                warn!("Backups of {} are stale, {}", service.label(), detail);
+                notify(service.notifications.as_ref(), &Notification::alert(NotificationEvent::Stale, &service.name, service.job.as_deref(), catalog.host(), detail, &[])).await;
+            }
+            Some(_) => {}
+            None => alerted.stale = false
+        }
+    }
+
+    // A running mysqldump has only recorded some of its databases yet.
+    if let Some(max_size_deviation) = backup_config.max_size_deviation {
+        if service.is_running().await {
+            return Ok(());
+        }
+        let window = backup_config.size_window.unwrap_or(DEFAULT_SIZE_WINDOW);
+        if let Some(deviation) = size_deviation(&backups, window) {
+            if deviation.percent.abs() > max_size_deviation && alerted.size_deviation != Some(deviation.run) {
+                alerted.size_deviation = Some(deviation.run);
+                let detail = format!("the latest backup is {}, {:+.0}% off the average of {}", format_size(deviation.size.max(0) as u64), deviation.percent, format_size(deviation.average as u64));
+                warn!("Backup size of {} changed, {}", service.label(), detail);
+                let run_backups = backups.into_iter().enumerate().filter(|(index, _)| deviation.backups.contains(index)).map(|(_, backup)| backup).collect::<Vec<_>>();
+                notify(service.notifications.as_ref(), &Notification::alert(NotificationEvent::SizeDeviation, &service.name, service.job.as_deref(), catalog.host(), detail, &run_backups)).await;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Watches the catalog in the background for services whose backups stopped or changed size,
+/// which catches a wrong schedule or a wedged scheduler that never runs a job to fail.
+pub fn spawn(services: Vec<Arc<MySQLService>>, catalog: &'static dyn Catalog) {
+    let services = services.iter()
+        .flat_map(|service| service.job_services())
+        .filter(|service| service.backup.as_ref().is_some_and(|backup| backup.max_age.is_some() || backup.max_size_deviation.is_some()))
+        .collect::<Vec<_>>();
+    if services.is_empty() {
+        return;
+    }
+
+    info!("Watching the backups of {} job(s).", services.len());
+    tokio::spawn(async move {
+        let since = Utc::now().naive_utc();
+        let mut alerted: HashMap<String, Alerted> = HashMap::new();
+        let mut ticker = interval(CHECK_INTERVAL);
+        loop {
+            ticker.tick().await;
+            for service in &services {
+                let alerted = alerted.entry(service.label()).or_default();
+                if let Err(error) = check_service(service, catalog, since, alerted).await {
+                    error!("Failed to check the backups of {}, error: {}", service.label(), error);
+                }
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sqlx::types::chrono::NaiveDate;
+
+    fn at(day: u32, hour: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2024, 4, day).unwrap().and_hms_opt(hour, 0, 0).unwrap()
+    }
+
+    fn backup(uuid: u128, run: u128, checkpoint_type: &str, size: i64) -> MysqlBackupRow {
+        MysqlBackupRow {
+            size,
+            created_at: at(1, 0),
+            checkpoint_type: Some(checkpoint_type.to_string()),
+            run_uuid: Some(Uuid::from_u128(run)),
+            ..MysqlBackupRow::test_default(uuid)
+        }
+    }
+
+    #[test]
+    fn test_stale_for() {
+        let max_age = Duration::from_secs(26 * 3600);
+        assert_eq!(stale_for(Some(at(1, 0)), at(1, 0), max_age, at(2, 1)), None);
+        assert_eq!(stale_for(Some(at(1, 0)), at(1, 0), max_age, at(2, 3)), Some(Duration::from_secs(27 * 3600)));
+        // Without backups the clock starts when watching does.
+        assert_eq!(stale_for(None, at(2, 0), max_age, at(2, 3)), None);
+    }
+
+    #[test]
+    fn test_size_deviation() {
+        // Incrementals are only compared with incrementals.
+        let backups = [backup(1, 1, "full", 1000), backup(2, 2, "incremental", 100), backup(3, 3, "incremental", 300), backup(4, 4, "incremental", 400)];
+        let deviation = size_deviation(&backups, 7).unwrap();
+        assert_eq!(deviation.run, Uuid::from_u128(4));
+        assert_eq!(deviation.average, 200.0);
+        assert_eq!(deviation.percent, 100.0);
+        assert!((size_deviation(&backups, 1).unwrap().percent - 100.0 / 3.0).abs() < 1e-9);
+
+        // Backups of one run are summed, a first run has nothing to compare with.
+        let backups = [backup(1, 1, "full", 500), backup(2, 1, "full", 500), backup(3, 2, "full", 600), backup(4, 2, "full", 300)];
+        assert_eq!(size_deviation(&backups, 7).unwrap().percent, -10.0);
+        assert_eq!(size_deviation(&backups[..2], 7), None);
+    }
+}
@@ -0,0 +1,22 @@
+use std::process::Command;
+use tempfile::tempdir;
+
+#[test]
+fn test_config_check_with_explicit_paths() {
+    let dir = tempdir().unwrap();
+    let config_path = dir.path().join("config.toml");
+    std::fs::write(&config_path, "[backup]\nbasedir = \"/srv\"\n").unwrap();
+
+    // Installed binaries have no manifest directory to fall back to, the given paths have to do.
+    let status = Command::new(env!("CARGO_BIN_EXE_mysql-backup-manager"))
+        .env_remove("CARGO_MANIFEST_DIR")
+        .env_remove("RUST_ENV")
+        .arg("--config")
+        .arg(&config_path)
+        .arg("--data-dir")
+        .arg(dir.path())
+        .args(["config", "check"])
+        .status()
+        .unwrap();
+    assert!(status.success());
+}